@@ -9,6 +9,7 @@ fn main() {
         "filter1.frag",
         "filter2.frag",
         "filter3.frag",
+        "octree.frag",
         "simulation.comp",
     ];
     let src_dir = std::env::current_dir()
@@ -0,0 +1,240 @@
+use crate::math::{Matrix, Vector};
+use std::marker::PhantomData;
+
+// Layout rules of GLSL interface blocks, which differ in how arrays and
+// structs are aligned
+pub trait Layout {
+    const MIN_AGGREGATE_ALIGN: usize;
+}
+
+// Uniform blocks, arrays and structs are aligned like `vec4`
+#[derive(Debug, Clone, Copy)]
+pub struct Std140;
+
+// Storage blocks and push constants, arrays and structs are aligned like their members
+#[derive(Debug, Clone, Copy)]
+pub struct Std430;
+
+impl Layout for Std140 {
+    const MIN_AGGREGATE_ALIGN: usize = 16;
+}
+
+impl Layout for Std430 {
+    const MIN_AGGREGATE_ALIGN: usize = 1;
+}
+
+// Types which have a GLSL counterpart laid out by `L`
+pub trait GpuLayout<L: Layout> {
+    const ALIGN: usize;
+    const SIZE: usize;
+    // Member names and offsets for structs declared with `gpu_layout!`
+    const MEMBERS: &'static [(&'static str, usize)] = &[];
+
+    // `out` is `SIZE` bytes long, padding is left as it is
+    fn write(&self, out: &mut [u8]);
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![0; Self::SIZE];
+        self.write(&mut out);
+        out
+    }
+}
+
+// Scalars which can make up vectors and matrices
+pub trait Scalar {}
+
+pub const fn round_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+pub const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+// Offset of the member `name`, `usize::MAX` if there is none
+pub const fn member_offset(members: &[(&str, usize)], name: &str) -> usize {
+    let mut i = 0;
+    while i < members.len() {
+        let (member, offset) = members[i];
+        if str_eq(member, name) {
+            return offset;
+        }
+        i += 1;
+    }
+    usize::MAX
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+macro_rules! impl_scalar {
+    ($ty:ty) => {
+        impl Scalar for $ty {}
+
+        impl<L: Layout> GpuLayout<L> for $ty {
+            const ALIGN: usize = 4;
+            const SIZE: usize = 4;
+
+            fn write(&self, out: &mut [u8]) {
+                out.copy_from_slice(&self.to_ne_bytes());
+            }
+        }
+    };
+}
+
+impl_scalar!(f32);
+impl_scalar!(i32);
+impl_scalar!(u32);
+
+// `vec3` takes 12 bytes but is aligned to 16
+impl<L: Layout, T: Copy + Scalar + GpuLayout<L>, const N: usize> GpuLayout<L> for Vector<T, N> {
+    const ALIGN: usize = T::SIZE
+        * match N {
+            1 => 1,
+            2 => 2,
+            3 | 4 => 4,
+            _ => panic!("GLSL vectors have at most 4 components"),
+        };
+    const SIZE: usize = T::SIZE * N;
+
+    fn write(&self, out: &mut [u8]) {
+        for (i, x) in self.0.iter().enumerate() {
+            x.write(&mut out[T::SIZE * i..][..T::SIZE]);
+        }
+    }
+}
+
+impl<L: Layout, T: GpuLayout<L>, const K: usize> GpuLayout<L> for [T; K] {
+    const ALIGN: usize = max(T::ALIGN, L::MIN_AGGREGATE_ALIGN);
+    const SIZE: usize = K * ArrayStride::<L, T>::STRIDE;
+
+    fn write(&self, out: &mut [u8]) {
+        let stride = ArrayStride::<L, T>::STRIDE;
+        for (i, x) in self.iter().enumerate() {
+            x.write(&mut out[stride * i..][..T::SIZE]);
+        }
+    }
+}
+
+struct ArrayStride<L, T>(PhantomData<(L, T)>);
+
+impl<L: Layout, T: GpuLayout<L>> ArrayStride<L, T> {
+    const STRIDE: usize = round_up(T::SIZE, max(T::ALIGN, L::MIN_AGGREGATE_ALIGN));
+}
+
+// Column-major, laid out as an array of its column vectors
+impl<L: Layout, T: Copy + Scalar + GpuLayout<L>, const N: usize, const M: usize> GpuLayout<L>
+    for Matrix<T, N, M>
+{
+    const ALIGN: usize = <[Vector<T, N>; M] as GpuLayout<L>>::ALIGN;
+    const SIZE: usize = <[Vector<T, N>; M] as GpuLayout<L>>::SIZE;
+
+    fn write(&self, out: &mut [u8]) {
+        GpuLayout::<L>::write(&self.0.map(Vector), out);
+    }
+}
+
+// Declares a struct along with its `GpuLayout` for every layout, members are
+// placed in the order of declaration like in GLSL
+macro_rules! gpu_layout {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($member_vis:vis $member:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($member_vis $member: $ty),*
+        }
+
+        impl<L: $crate::layout::Layout> $crate::layout::GpuLayout<L> for $name
+        where
+            $($ty: $crate::layout::GpuLayout<L>),*
+        {
+            const ALIGN: usize = {
+                use $crate::layout::{max, GpuLayout};
+                let mut align = L::MIN_AGGREGATE_ALIGN;
+                $(align = max(align, <$ty as GpuLayout<L>>::ALIGN);)*
+                align
+            };
+            const SIZE: usize = {
+                use $crate::layout::{round_up, GpuLayout};
+                let mut size = 0;
+                $(
+                    size = round_up(size, <$ty as GpuLayout<L>>::ALIGN);
+                    size += <$ty as GpuLayout<L>>::SIZE;
+                )*
+                round_up(size, Self::ALIGN)
+            };
+            const MEMBERS: &'static [(&'static str, usize)] = &{
+                use $crate::layout::{round_up, GpuLayout};
+                let mut members = [$((stringify!($member), 0)),*];
+                let mut i = 0;
+                let mut size = 0;
+                $(
+                    members[i].1 = round_up(size, <$ty as GpuLayout<L>>::ALIGN);
+                    size = members[i].1 + <$ty as GpuLayout<L>>::SIZE;
+                    i += 1;
+                )*
+                let _ = (i, size);
+                members
+            };
+
+            fn write(&self, out: &mut [u8]) {
+                use $crate::layout::GpuLayout;
+                let members = <Self as GpuLayout<L>>::MEMBERS;
+                let mut i = 0;
+                $(
+                    let size = <$ty as GpuLayout<L>>::SIZE;
+                    GpuLayout::<L>::write(&self.$member, &mut out[members[i].1..][..size]);
+                    i += 1;
+                )*
+                let _ = i;
+            }
+        }
+    };
+}
+
+// Fails to compile unless the members of `$name` are at the given offsets and
+// it has the given size, which are to be taken from the GLSL declaration
+macro_rules! assert_gpu_layout {
+    ($layout:ty, $name:ty { $($member:ident: $offset:expr),* $(,)? }, $size:expr) => {
+        const _: () = {
+            let members = <$name as $crate::layout::GpuLayout<$layout>>::MEMBERS;
+            assert!(
+                members.len() == [$($offset),*].len(),
+                concat!("`", stringify!($name), "` has members missing in GLSL"),
+            );
+            $(assert!(
+                $crate::layout::member_offset(members, stringify!($member)) == $offset,
+                concat!(
+                    "`", stringify!($name), "::", stringify!($member), "` is not where GLSL has it"
+                ),
+            );)*
+            assert!(
+                <$name as $crate::layout::GpuLayout<$layout>>::SIZE == $size,
+                concat!("`", stringify!($name), "` differs in size from GLSL"),
+            );
+        };
+    };
+}
+
+pub(crate) use assert_gpu_layout;
+pub(crate) use gpu_layout;
@@ -1,46 +1,116 @@
+mod layout;
 mod math;
 mod state;
 mod vkapp;
 mod vklib;
 mod voxel;
+#[allow(unused)]
+mod voxel_bintree;
+#[allow(unused)]
+mod voxel_quadtree;
 
 use ash::vk;
+use layout::{assert_gpu_layout, gpu_layout, GpuLayout, Std140, Std430};
 use math::{mat4, vec3, vec4, Vector};
 use sdl2::event::Event;
 use state::StateBox;
 use std::{mem, ptr, slice, time};
 use vkapp::{
     create_descriptor_pool, create_descriptor_sets_filter, create_descriptor_sets_main,
-    create_descriptor_sets_simulation, create_render_pass, update_descriptor_sets_filter,
-    PipelineBox, PipelineVec, Swapchain,
+    create_descriptor_sets_octree, create_descriptor_sets_simulation, create_render_pass,
+    update_descriptor_sets_filter, update_descriptor_sets_octree, OctreeNodePool, PipelineBox,
+    PipelineVec, Swapchain,
 };
 use vklib::{CommittedBuffer, SdlContext, VkContext};
+use voxel::{octree::Octree, palette::Palette};
 
 const MAX_CONCURRENT_FRAMES: usize = 2;
 const MAX_PARTICLE_COUNT: usize = 1 << 16;
+const MAX_PALETTE_SIZE: usize = 256;
+const OCTREE_POOL_CAPACITY: usize = 1 << 18;
+const OCTREE_STAGING_SIZE: usize = 1 << 20;
+
+gpu_layout! {
+    #[derive(Clone, Copy, Debug, Default)]
+    struct CameraData {
+        mat_view: mat4,
+        mat_proj: mat4,
+        mat_view_proj: mat4,
+    }
+}
 
-#[derive(Clone, Copy, Debug, Default)]
-struct CameraData {
-    mat_view: mat4,
-    mat_proj: mat4,
-    mat_view_proj: mat4,
+gpu_layout! {
+    #[derive(Clone, Copy, Debug, Default)]
+    struct SimulationStepParams {
+        init_pos: vec4, // w --- acceptable deviation
+        init_vel: vec4, // w --- acceptable deviation
+        acc: vec4,      // w --- unused
+        particle_count: u32,
+        rng_seed: u32,
+        time_step: f32,
+        init_ttl: f32,
+    }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
-struct SimulationStepParams {
-    init_pos: vec4, // w --- acceptable deviation
-    init_vel: vec4, // w --- acceptable deviation
-    acc: vec4,      // w --- unused
-    particle_count: u32,
-    rng_seed: u32,
-    time_step: f32,
-    init_ttl: f32,
+gpu_layout! {
+    #[derive(Clone, Copy, Debug, Default)]
+    struct OctreeParams {
+        transform: vec4, // xyz --- world position of the octree origin, w --- voxel size
+    }
+}
+
+gpu_layout! {
+    #[derive(Clone, Copy, Debug, Default)]
+    struct FilterParams {
+        screen_data: vec4,   // xy --- screen size, zw = 1/xy --- pixel size
+        kernel_radius: vec4, // xy --- box blur kernel radius, zw --- unused
+    }
 }
 
+// As in `CameraBuffer` of the shaders
+assert_gpu_layout!(
+    Std140,
+    CameraData {
+        mat_view: 0,
+        mat_proj: 64,
+        mat_view_proj: 128,
+    },
+    192
+);
+
+// As in `SimulationStepParams` of simulation.comp
+assert_gpu_layout!(
+    Std140,
+    SimulationStepParams {
+        init_pos: 0,
+        init_vel: 16,
+        acc: 32,
+        particle_count: 48,
+        rng_seed: 52,
+        time_step: 56,
+        init_ttl: 60,
+    },
+    64
+);
+
+// As in `Params` of octree.frag
+assert_gpu_layout!(Std430, OctreeParams { transform: 0 }, 16);
+
+// As in `Params` of filter0.frag to filter3.frag
+assert_gpu_layout!(
+    Std430,
+    FilterParams {
+        screen_data: 0,
+        kernel_radius: 16,
+    },
+    32
+);
+
 #[derive(Clone, Copy, Debug, Default)]
 struct Vertex {
     pos: vec3,
     norm: vec3,
+    voxel: u32,
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -51,6 +121,7 @@ struct Particle {
 
 fn main() {
     let mut state = StateBox::load("state.json".into());
+    let mut palette = Palette::try_load("palette.json").unwrap_or_default();
 
     unsafe {
         let mut sdl = SdlContext::new();
@@ -101,6 +172,7 @@ fn main() {
         let pipeline_simulate = PipelineBox::new_simulation(&vk);
         // let pipeline_main = PipelineBox::new_main(&vk, render_pass.0, msaa_sample_count);
         let pipeline_particle = PipelineBox::new_particle(&vk, render_pass.0, msaa_sample_count);
+        let pipeline_octree = PipelineBox::new_octree(&vk, render_pass.0, msaa_sample_count);
         let pipeline_filter = PipelineVec::new_filters(&vk, render_pass.0);
 
         let mut imgui = imgui::Context::create();
@@ -131,6 +203,18 @@ fn main() {
             vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER,
         );
 
+        let mut octree = Octree::new();
+        octree.set([0, 0, 0], [64, 4, 64], 0);
+        octree.set([8, 4, 8], [8, 24, 8], 1);
+        octree.set([40, 4, 24], [16, 16, 16], 2);
+        let mut octree_pool = OctreeNodePool::new(
+            &vk,
+            command_pool_transient.0,
+            &mut octree,
+            OCTREE_POOL_CAPACITY,
+            OCTREE_STAGING_SIZE,
+        );
+
         let (index_buffer, n_indices) = {
             let indices = [0u32, 1, 3, 3, 2, 0];
             (
@@ -145,10 +229,10 @@ fn main() {
         };
 
         let mut camera_data = CameraData::default();
-        let camera_data_size = mem::size_of_val(&camera_data);
+        let camera_data_size = <CameraData as GpuLayout<Std140>>::SIZE;
 
         let mut simulation_params = SimulationStepParams::default();
-        let simulation_params_size = mem::size_of_val(&simulation_params);
+        let simulation_params_size = <SimulationStepParams as GpuLayout<Std140>>::SIZE;
 
         let command_buffers =
             vk.allocate_command_buffers(command_pool.0, MAX_CONCURRENT_FRAMES as _);
@@ -156,6 +240,12 @@ fn main() {
         let mut camera_mappings = Vec::with_capacity(MAX_CONCURRENT_FRAMES);
         let mut simulation_params_buffers = Vec::with_capacity(MAX_CONCURRENT_FRAMES);
         let mut simulation_params_mappings = Vec::with_capacity(MAX_CONCURRENT_FRAMES);
+        let mut palette_data = palette.gpu_data(MAX_PALETTE_SIZE);
+        let palette_data_size = mem::size_of_val(&palette_data[..]);
+        // Per frame in flight, whether its buffer is behind `palette_data`
+        let mut palette_stale = [true; MAX_CONCURRENT_FRAMES];
+        let mut palette_buffers = Vec::with_capacity(MAX_CONCURRENT_FRAMES);
+        let mut palette_mappings = Vec::with_capacity(MAX_CONCURRENT_FRAMES);
         let mut semaphores_image_available = Vec::with_capacity(MAX_CONCURRENT_FRAMES);
         let mut semaphores_render_finished = Vec::with_capacity(MAX_CONCURRENT_FRAMES);
         let mut fences_in_flight = Vec::with_capacity(MAX_CONCURRENT_FRAMES);
@@ -196,6 +286,24 @@ fn main() {
             simulation_params_mappings.push(memory_mapping);
             simulation_params_buffers.push(buffer);
 
+            let buffer = CommittedBuffer::new(
+                &vk,
+                palette_data_size as _,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+            let memory_mapping = vk
+                .device
+                .map_memory(
+                    buffer.memory.0,
+                    0,
+                    palette_data_size as _,
+                    vk::MemoryMapFlags::empty(),
+                )
+                .unwrap();
+            palette_mappings.push(memory_mapping);
+            palette_buffers.push(buffer);
+
             semaphores_image_available.push(vk.create_semaphore());
             semaphores_render_finished.push(vk.create_semaphore());
             fences_in_flight.push(vk.create_fence_signaled());
@@ -209,11 +317,12 @@ fn main() {
             &simulation_params_buffers,
             particles_buffer.buffer.0,
         );
-        // let descriptor_sets_main = create_descriptor_sets_main(
+        // let descriptor_sets_main = create_descriptor_sets_mesh(
         //     &vk,
         //     descriptor_pool.0,
         //     pipeline_main.descriptor_set_layout.0,
-        //     &uniform_buffers,
+        //     &camera_buffers,
+        //     &palette_buffers,
         // );
         let descriptor_sets_particle = create_descriptor_sets_main(
             &vk,
@@ -221,6 +330,14 @@ fn main() {
             pipeline_particle.descriptor_set_layout.0,
             &camera_buffers,
         );
+        let descriptor_sets_octree = create_descriptor_sets_octree(
+            &vk,
+            descriptor_pool.0,
+            pipeline_octree.descriptor_set_layout.0,
+            &camera_buffers,
+            octree_pool.buffer.buffer.0,
+            &palette_buffers,
+        );
         let descriptor_sets_filter = create_descriptor_sets_filter(
             &vk,
             descriptor_pool.0,
@@ -280,6 +397,9 @@ fn main() {
                     .build();
                 ui.slider("Turn speed", -360.0, 360.0, &mut state.turn_speed);
                 ui.slider("Angle", -180.0, 180.0, &mut state.angle_deg);
+                ui.slider("Field of view", 10.0, 170.0, &mut state.fov_deg);
+                ui.input_float("Near plane", &mut state.z_near).build();
+                ui.input_float("Far plane", &mut state.z_far).build();
 
                 ui.spacing();
 
@@ -301,6 +421,19 @@ fn main() {
                 ui.spacing();
 
                 ui.slider("Blur radius", 0, 128, &mut state.blur_radius);
+
+                ui.spacing();
+
+                ui.input_float3("Octree origin", &mut state.octree_origin.0)
+                    .build();
+                ui.slider("Voxel size", 0.001, 0.1, &mut state.voxel_size);
+
+                ui.spacing();
+
+                if palette.edit(ui) {
+                    palette_data = palette.gpu_data(MAX_PALETTE_SIZE);
+                    palette_stale = [true; MAX_CONCURRENT_FRAMES];
+                }
             });
 
             let time_elapsed = time_curr - time_prev;
@@ -316,33 +449,14 @@ fn main() {
                     state.orbit_distance.y(),
                     cos * state.orbit_distance.x(),
                 ]);
-            let look_at = state.orbit_center;
-            let world_up = Vector([0.0, 1.0, 0.0]);
-            let cam_forward = (look_at - cam_pos).normalize();
-            let cam_right = cam_forward.cross(world_up).normalize();
-            let cam_down = cam_forward.cross(cam_right);
-
-            camera_data.mat_view.0[0][0] = cam_right.x();
-            camera_data.mat_view.0[1][0] = cam_right.y();
-            camera_data.mat_view.0[2][0] = cam_right.z();
-            camera_data.mat_view.0[3][0] = -cam_right.dot(cam_pos);
-            camera_data.mat_view.0[0][1] = cam_down.x();
-            camera_data.mat_view.0[1][1] = cam_down.y();
-            camera_data.mat_view.0[2][1] = cam_down.z();
-            camera_data.mat_view.0[3][1] = -cam_down.dot(cam_pos);
-            camera_data.mat_view.0[0][2] = cam_forward.x();
-            camera_data.mat_view.0[1][2] = cam_forward.y();
-            camera_data.mat_view.0[2][2] = cam_forward.z();
-            camera_data.mat_view.0[3][2] = -cam_forward.dot(cam_pos);
-            camera_data.mat_view.0[0][3] = 0.0;
-            camera_data.mat_view.0[1][3] = 0.0;
-            camera_data.mat_view.0[2][3] = 0.0;
-            camera_data.mat_view.0[3][3] = 1.0;
-
-            camera_data.mat_proj = mat4::identity();
-            camera_data.mat_proj.0[0][0] =
-                swapchain.extent.height as f32 / swapchain.extent.width as f32;
-            camera_data.mat_proj.0[2][3] = 1.0;
+            camera_data.mat_view =
+                mat4::look_at(cam_pos, state.orbit_center, Vector([0.0, 1.0, 0.0]));
+            camera_data.mat_proj = mat4::perspective(
+                state.fov_deg.to_radians(),
+                swapchain.extent.width as f32 / swapchain.extent.height as f32,
+                state.z_near,
+                state.z_far,
+            );
             camera_data.mat_view_proj = camera_data.mat_proj.dot(&camera_data.mat_view);
 
             simulation_params.particle_count = state.particle_count;
@@ -360,18 +474,16 @@ fn main() {
             // let cur_descriptor_set_main = descriptor_sets_main[frame_in_flight_index];
             let cur_descriptor_set_simulation = descriptor_sets_simulation[frame_in_flight_index];
             let cur_descriptor_set_particle = descriptor_sets_particle[frame_in_flight_index];
+            let cur_descriptor_set_octree = descriptor_sets_octree[frame_in_flight_index];
 
             ptr::copy(
-                mem::transmute::<*const CameraData, *const std::ffi::c_void>(
-                    &camera_data as *const _,
-                ),
+                GpuLayout::<Std140>::to_bytes(&camera_data).as_ptr() as *const std::ffi::c_void,
                 camera_mappings[frame_in_flight_index],
                 camera_data_size,
             );
             ptr::copy(
-                mem::transmute::<*const SimulationStepParams, *const std::ffi::c_void>(
-                    &simulation_params as *const _,
-                ),
+                GpuLayout::<Std140>::to_bytes(&simulation_params).as_ptr()
+                    as *const std::ffi::c_void,
                 simulation_params_mappings[frame_in_flight_index],
                 simulation_params_size,
             );
@@ -379,6 +491,14 @@ fn main() {
             vk.device
                 .wait_for_fences(slice::from_ref(&cur_fence), true, u64::MAX)
                 .unwrap();
+            if palette_stale[frame_in_flight_index] {
+                ptr::copy(
+                    palette_data.as_ptr() as *const std::ffi::c_void,
+                    palette_mappings[frame_in_flight_index],
+                    palette_data_size,
+                );
+                palette_stale[frame_in_flight_index] = false;
+            }
             let result = vk.device_ext_swapchain.acquire_next_image(
                 swapchain.swapchain.0,
                 u64::MAX,
@@ -400,6 +520,27 @@ fn main() {
                 Err(err) => panic!("Unexpected Vulkan error: {err}"),
             };
 
+            if !octree_pool.queue(&mut octree) {
+                // The pool is replaced by a larger one holding the whole tree
+                vk.device.device_wait_idle().unwrap();
+                let mut capacity = 2 * octree_pool.capacity();
+                while capacity < octree.capacity() {
+                    capacity *= 2;
+                }
+                octree_pool = OctreeNodePool::new(
+                    &vk,
+                    command_pool_transient.0,
+                    &mut octree,
+                    capacity,
+                    OCTREE_STAGING_SIZE,
+                );
+                update_descriptor_sets_octree(
+                    &vk,
+                    &descriptor_sets_octree,
+                    octree_pool.buffer.buffer.0,
+                );
+            }
+
             vk.device
                 .reset_command_buffer(cur_command_buffer, vk::CommandBufferResetFlags::empty())
                 .unwrap();
@@ -407,6 +548,8 @@ fn main() {
             vk.device
                 .begin_command_buffer(cur_command_buffer, &begin_info)
                 .unwrap();
+
+            octree_pool.record(&vk, cur_command_buffer, frame_in_flight_index);
             let clear_values = [
                 vk::ClearValue::default(),
                 vk::ClearValue {
@@ -468,6 +611,31 @@ fn main() {
                 &render_pass_begin,
                 vk::SubpassContents::INLINE,
             );
+            vk.device.cmd_bind_pipeline(
+                cur_command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline_octree.pipeline.0,
+            );
+            vk.device.cmd_bind_descriptor_sets(
+                cur_command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline_octree.layout.0,
+                0,
+                &[cur_descriptor_set_octree],
+                &[],
+            );
+            let push_constants = OctreeParams {
+                transform: state.octree_origin.extend(state.voxel_size),
+            };
+            vk.device.cmd_push_constants(
+                cur_command_buffer,
+                pipeline_octree.layout.0,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                &GpuLayout::<Std430>::to_bytes(&push_constants),
+            );
+            vk.device.cmd_draw(cur_command_buffer, 3, 1, 0, 0);
+
             vk.device.cmd_bind_pipeline(
                 cur_command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
@@ -518,21 +686,26 @@ fn main() {
                     &[descriptor_sets_filter[i_filter % 2]],
                     &[],
                 );
-                let push_constants = [
-                    Vector([
+                let push_constants = FilterParams {
+                    screen_data: Vector([
                         swapchain.extent.width as f32,
                         swapchain.extent.height as f32,
                         1.0 / swapchain.extent.width as f32,
                         1.0 / swapchain.extent.height as f32,
                     ]),
-                    Vector([state.blur_radius as f32, state.blur_radius as f32, 0.0, 0.0]),
-                ];
+                    kernel_radius: Vector([
+                        state.blur_radius as f32,
+                        state.blur_radius as f32,
+                        0.0,
+                        0.0,
+                    ]),
+                };
                 vk.device.cmd_push_constants(
                     cur_command_buffer,
                     pipeline_filter.layout.0,
                     vk::ShaderStageFlags::FRAGMENT,
                     0,
-                    push_constants.align_to().1,
+                    &GpuLayout::<Std430>::to_bytes(&push_constants),
                 );
                 vk.device.cmd_draw(cur_command_buffer, 3, 1, 0, 0);
             }
@@ -581,4 +754,9 @@ fn main() {
 
         vk.device.device_wait_idle().unwrap();
     }
+
+    match palette.try_save("palette.json") {
+        Ok(()) => {}
+        Err(err) => eprintln!("{err}"),
+    }
 }
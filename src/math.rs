@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Debug,
-    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+    ops::{
+        Add, AddAssign, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Div,
+        DivAssign, Mul, MulAssign, Neg, Not, Shl, ShlAssign, Shr, ShrAssign, Sub, SubAssign,
+    },
 };
 
 #[derive(Clone, Copy, Debug)]
@@ -13,6 +16,18 @@ pub struct Matrix<T: Copy, const N: usize, const M: usize>(
     pub [[T; N]; M],
 );
 
+// Stored as `[x, y, z, w]`, rotations are unit quaternions
+#[derive(Clone, Copy, Debug)]
+pub struct Quaternion<T: Copy>(pub Vector<T, 4>);
+
+// Scales first, then rotates and translates
+#[derive(Clone, Copy, Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct Transform<T: Copy> {
+    pub translation: Vector<T, 3>,
+    pub rotation: Quaternion<T>,
+    pub scale: Vector<T, 3>,
+}
+
 #[allow(unused, non_camel_case_types)]
 pub type vec2 = Vector<f32, 2>;
 #[allow(unused, non_camel_case_types)]
@@ -26,9 +41,17 @@ pub type ivec3 = Vector<i32, 3>;
 #[allow(unused, non_camel_case_types)]
 pub type ivec4 = Vector<i32, 4>;
 #[allow(unused, non_camel_case_types)]
+pub type bvec2 = Vector<bool, 2>;
+#[allow(unused, non_camel_case_types)]
+pub type bvec3 = Vector<bool, 3>;
+#[allow(unused, non_camel_case_types)]
+pub type bvec4 = Vector<bool, 4>;
+#[allow(unused, non_camel_case_types)]
 pub type mat4x4 = Matrix<f32, 4, 4>;
 #[allow(unused, non_camel_case_types)]
 pub type mat4 = mat4x4;
+#[allow(unused, non_camel_case_types)]
+pub type quat = Quaternion<f32>;
 
 macro_rules! impl_scalar_op {
     ($trait:ident, $op:ident) => {
@@ -108,6 +131,78 @@ macro_rules! impl_op_assign {
     };
 }
 
+// Vectors only, with both vector and scalar right hand sides
+macro_rules! impl_bit_op {
+    ($trait:ident, $op:ident, $assign_trait:ident, $assign_op:ident) => {
+        impl<T: Copy + $trait<Output = T>, const N: usize> $trait for Vector<T, N> {
+            type Output = Self;
+            fn $op(mut self, rhs: Self) -> Self {
+                for i in 0..N {
+                    self.0[i] = self.0[i].$op(rhs.0[i]);
+                }
+                self
+            }
+        }
+
+        impl<T: Copy + $trait<Output = T>, const N: usize> $trait<T> for Vector<T, N> {
+            type Output = Self;
+            fn $op(mut self, rhs: T) -> Self {
+                for i in 0..N {
+                    self.0[i] = self.0[i].$op(rhs);
+                }
+                self
+            }
+        }
+
+        impl<T: Copy + $assign_trait, const N: usize> $assign_trait for Vector<T, N> {
+            fn $assign_op(&mut self, rhs: Self) {
+                for i in 0..N {
+                    self.0[i].$assign_op(rhs.0[i]);
+                }
+            }
+        }
+
+        impl<T: Copy + $assign_trait, const N: usize> $assign_trait<T> for Vector<T, N> {
+            fn $assign_op(&mut self, rhs: T) {
+                for i in 0..N {
+                    self.0[i].$assign_op(rhs);
+                }
+            }
+        }
+    };
+}
+
+// Each swizzle exists only for the sizes having all of its components
+macro_rules! impl_swizzle {
+    ($($name:ident: $k:literal $indices:tt for $($n:literal),*;)*) => {
+        $($(impl_swizzle!(@impl $n, $name, $k, $indices);)*)*
+    };
+    (@impl $n:literal, $name:ident, $k:literal, [$($i:literal),*]) => {
+        impl<T: Copy> Vector<T, $n> {
+            #[allow(unused)]
+            pub fn $name(&self) -> Vector<T, $k> {
+                Vector([$(self.0[$i]),*])
+            }
+        }
+    };
+}
+
+// `as` conversions between every pair of the types
+macro_rules! impl_cast {
+    ($($from:ty),*) => {
+        $(impl_cast!(@from $from; f32, f64, i32, u32, usize);)*
+    };
+    (@from $from:ty; $($to:ty),*) => {
+        $(
+            impl Cast<$to> for $from {
+                fn cast(self) -> $to {
+                    self as $to
+                }
+            }
+        )*
+    };
+}
+
 macro_rules! impl_fty {
     ($fty:ty) => {
         impl<const N: usize> Vector<$fty, N> {
@@ -120,6 +215,40 @@ macro_rules! impl_fty {
             pub fn normalize(self) -> Self {
                 self / self.length()
             }
+
+            #[allow(unused)]
+            pub fn floor(self) -> Self {
+                Self(self.0.map(<$fty>::floor))
+            }
+
+            #[allow(unused)]
+            pub fn ceil(self) -> Self {
+                Self(self.0.map(<$fty>::ceil))
+            }
+
+            #[allow(unused)]
+            pub fn fract(self) -> Self {
+                self - self.floor()
+            }
+
+            // 0 below `edge` and 1 elsewhere, as `step(edge, self)` in GLSL
+            #[allow(unused)]
+            pub fn step(self, edge: Self) -> Self {
+                Self(std::array::from_fn(|i| {
+                    if self.0[i] < edge.0[i] {
+                        0.0
+                    } else {
+                        1.0
+                    }
+                }))
+            }
+
+            // As `smoothstep(edge0, edge1, self)` in GLSL
+            #[allow(unused)]
+            pub fn smoothstep(self, edge0: Self, edge1: Self) -> Self {
+                let t = ((self - edge0) / (edge1 - edge0)).clamp(0.0.into(), 1.0.into());
+                t * t * (Self::from(3.0) - t * 2.0)
+            }
         }
 
         impl<const N: usize> Matrix<$fty, N, N> {
@@ -131,6 +260,342 @@ macro_rules! impl_fty {
                 }
                 out
             }
+
+            #[allow(unused)]
+            pub fn determinant(&self) -> $fty {
+                let mut m = *self;
+                let mut det = 1.0;
+                for i in 0..N {
+                    let pivot = m.pivot_row(i);
+                    if m.0[i][pivot] == 0.0 {
+                        return 0.0;
+                    }
+                    if pivot != i {
+                        m.swap_rows(i, pivot);
+                        det = -det;
+                    }
+                    det *= m.0[i][i];
+                    for row in i + 1..N {
+                        let k = m.0[i][row] / m.0[i][i];
+                        for col in i..N {
+                            m.0[col][row] -= k * m.0[col][i];
+                        }
+                    }
+                }
+                det
+            }
+
+            // Gauss-Jordan elimination, `None` for singular matrices
+            #[allow(unused)]
+            pub fn inverse(&self) -> Option<Self> {
+                let mut m = *self;
+                let mut out = Self::identity();
+                for i in 0..N {
+                    let pivot = m.pivot_row(i);
+                    if m.0[i][pivot] == 0.0 {
+                        return None;
+                    }
+                    m.swap_rows(i, pivot);
+                    out.swap_rows(i, pivot);
+                    let k = 1.0 / m.0[i][i];
+                    for col in 0..N {
+                        m.0[col][i] *= k;
+                        out.0[col][i] *= k;
+                    }
+                    for row in 0..N {
+                        let k = m.0[i][row];
+                        if row == i || k == 0.0 {
+                            continue;
+                        }
+                        for col in 0..N {
+                            m.0[col][row] -= k * m.0[col][i];
+                            out.0[col][row] -= k * out.0[col][i];
+                        }
+                    }
+                }
+                Some(out)
+            }
+
+            // Row at or below the diagonal with the largest absolute value in column `col`
+            fn pivot_row(&self, col: usize) -> usize {
+                (col..N)
+                    .max_by(|&a, &b| self.0[col][a].abs().total_cmp(&self.0[col][b].abs()))
+                    .unwrap()
+            }
+        }
+
+        // View space has x to the right, y down and z forward, like clip space.
+        // Depth is mapped to [0, 1], or to [1, 0] for the reversed variants
+        // which are meant for `GREATER` depth tests with depth cleared to 0.
+        impl Matrix<$fty, 4, 4> {
+            #[allow(unused)]
+            pub fn perspective(fov_y: $fty, aspect: $fty, near: $fty, far: $fty) -> Self {
+                Self::perspective_depth(
+                    fov_y,
+                    aspect,
+                    far / (far - near),
+                    near * far / (near - far),
+                )
+            }
+
+            #[allow(unused)]
+            pub fn perspective_reversed(fov_y: $fty, aspect: $fty, near: $fty, far: $fty) -> Self {
+                Self::perspective_depth(
+                    fov_y,
+                    aspect,
+                    near / (near - far),
+                    near * far / (far - near),
+                )
+            }
+
+            #[allow(unused)]
+            pub fn perspective_infinite(fov_y: $fty, aspect: $fty, near: $fty) -> Self {
+                Self::perspective_depth(fov_y, aspect, 1.0, -near)
+            }
+
+            #[allow(unused)]
+            pub fn perspective_infinite_reversed(fov_y: $fty, aspect: $fty, near: $fty) -> Self {
+                Self::perspective_depth(fov_y, aspect, 0.0, near)
+            }
+
+            // Depth is `a + b / z`
+            fn perspective_depth(fov_y: $fty, aspect: $fty, a: $fty, b: $fty) -> Self {
+                let f = 1.0 / (0.5 * fov_y).tan();
+                let mut out = Self::default();
+                out.0[0][0] = f / aspect;
+                out.0[1][1] = f;
+                out.0[2][2] = a;
+                out.0[3][2] = b;
+                out.0[2][3] = 1.0;
+                out
+            }
+
+            // Box `[lo, hi]` of view space to x and y in [-1, 1] and depth in [0, 1]
+            #[allow(unused)]
+            pub fn orthographic(lo: Vector<$fty, 3>, hi: Vector<$fty, 3>) -> Self {
+                let scale = Vector([2.0, 2.0, 1.0]) / (hi - lo);
+                let offset = Vector([-1.0, -1.0, 0.0]) - lo * scale;
+                let mut out = Self::identity();
+                for i in 0..3 {
+                    out.0[i][i] = scale.0[i];
+                    out.0[3][i] = offset.0[i];
+                }
+                out
+            }
+
+            // View matrix of a camera at `eye` facing `target`, with `up` pointing
+            // up on the screen
+            #[allow(unused)]
+            pub fn look_at(
+                eye: Vector<$fty, 3>,
+                target: Vector<$fty, 3>,
+                up: Vector<$fty, 3>,
+            ) -> Self {
+                let forward = (target - eye).normalize();
+                let right = forward.cross(up).normalize();
+                let down = forward.cross(right);
+                let mut out = Self::identity();
+                for (row, axis) in [right, down, forward].into_iter().enumerate() {
+                    for col in 0..3 {
+                        out.0[col][row] = axis.0[col];
+                    }
+                    out.0[3][row] = -axis.dot(eye);
+                }
+                out
+            }
+        }
+
+        impl Default for Quaternion<$fty> {
+            fn default() -> Self {
+                Self::identity()
+            }
+        }
+
+        impl Quaternion<$fty> {
+            #[allow(unused)]
+            pub fn identity() -> Self {
+                Self(Vector([0.0, 0.0, 0.0, 1.0]))
+            }
+
+            // Counter-clockwise by `angle` radians when looking against `axis`
+            #[allow(unused)]
+            pub fn from_axis_angle(axis: Vector<$fty, 3>, angle: $fty) -> Self {
+                let (sin, cos) = (0.5 * angle).sin_cos();
+                let [x, y, z] = (axis.normalize() * sin).0;
+                Self(Vector([x, y, z, cos]))
+            }
+
+            // Angles in radians around x, y and z, applied in the order z, x, y
+            // which is roll, pitch and yaw for a y-up world
+            #[allow(unused)]
+            pub fn from_euler(angles: Vector<$fty, 3>) -> Self {
+                let [x, y, z] = angles.0;
+                Self::from_axis_angle(Vector([0.0, 1.0, 0.0]), y)
+                    * Self::from_axis_angle(Vector([1.0, 0.0, 0.0]), x)
+                    * Self::from_axis_angle(Vector([0.0, 0.0, 1.0]), z)
+            }
+
+            // Rotation taking the coordinate axes to the orthonormal right-handed
+            // basis `x`, `y`, `z`
+            #[allow(unused)]
+            pub fn from_basis(x: Vector<$fty, 3>, y: Vector<$fty, 3>, z: Vector<$fty, 3>) -> Self {
+                // Divides by the largest of the components to stay accurate
+                let trace = x.x() + y.y() + z.z();
+                let q = if trace > 0.0 {
+                    let s = 2.0 * (1.0 + trace).sqrt();
+                    [
+                        (y.z() - z.y()) / s,
+                        (z.x() - x.z()) / s,
+                        (x.y() - y.x()) / s,
+                        0.25 * s,
+                    ]
+                } else if x.x() > y.y() && x.x() > z.z() {
+                    let s = 2.0 * (1.0 + x.x() - y.y() - z.z()).sqrt();
+                    [
+                        0.25 * s,
+                        (y.x() + x.y()) / s,
+                        (z.x() + x.z()) / s,
+                        (y.z() - z.y()) / s,
+                    ]
+                } else if y.y() > z.z() {
+                    let s = 2.0 * (1.0 + y.y() - x.x() - z.z()).sqrt();
+                    [
+                        (y.x() + x.y()) / s,
+                        0.25 * s,
+                        (z.y() + y.z()) / s,
+                        (z.x() - x.z()) / s,
+                    ]
+                } else {
+                    let s = 2.0 * (1.0 + z.z() - x.x() - y.y()).sqrt();
+                    [
+                        (z.x() + x.z()) / s,
+                        (z.y() + y.z()) / s,
+                        0.25 * s,
+                        (x.y() - y.x()) / s,
+                    ]
+                };
+                Self(Vector(q)).normalize()
+            }
+
+            #[allow(unused)]
+            pub fn normalize(self) -> Self {
+                Self(self.0.normalize())
+            }
+
+            #[allow(unused)]
+            pub fn conjugate(self) -> Self {
+                let [x, y, z, w] = self.0 .0;
+                Self(Vector([-x, -y, -z, w]))
+            }
+
+            #[allow(unused)]
+            pub fn inverse(self) -> Self {
+                Self(self.conjugate().0 / self.0.dot(self.0))
+            }
+
+            #[allow(unused)]
+            pub fn rotate(self, v: Vector<$fty, 3>) -> Vector<$fty, 3> {
+                let [x, y, z, w] = self.0 .0;
+                let u = Vector([x, y, z]);
+                let t = u.cross(v) * 2.0;
+                v + t * w + u.cross(t)
+            }
+
+            // Constant angular speed from `self` at 0 to `other` at 1, along the
+            // shorter arc
+            #[allow(unused)]
+            pub fn slerp(self, other: Self, t: $fty) -> Self {
+                let mut cos = self.0.dot(other.0);
+                let mut other = other.0;
+                // `q` and `-q` are the same rotation
+                if cos < 0.0 {
+                    cos = -cos;
+                    other = -other;
+                }
+                // Nearly the same rotation, where the division below is unstable
+                if cos > 0.9995 {
+                    return Self(self.0 + (other - self.0) * t).normalize();
+                }
+                let angle = cos.acos();
+                let a = ((1.0 - t) * angle).sin();
+                let b = (t * angle).sin();
+                Self((self.0 * a + other * b) / angle.sin())
+            }
+
+            #[allow(unused)]
+            pub fn to_mat4(self) -> Matrix<$fty, 4, 4> {
+                let mut out = Matrix::<$fty, 4, 4>::identity();
+                for i in 0..3 {
+                    let mut axis = Vector([0.0; 3]);
+                    axis.0[i] = 1.0;
+                    out.0[i][..3].copy_from_slice(&self.rotate(axis).0);
+                }
+                out
+            }
+        }
+
+        impl Default for Transform<$fty> {
+            fn default() -> Self {
+                Self::identity()
+            }
+        }
+
+        impl Transform<$fty> {
+            #[allow(unused)]
+            pub fn identity() -> Self {
+                Self {
+                    translation: Vector([0.0; 3]),
+                    rotation: Quaternion::<$fty>::identity(),
+                    scale: Vector([1.0; 3]),
+                }
+            }
+
+            #[allow(unused)]
+            pub fn transform_point(self, p: Vector<$fty, 3>) -> Vector<$fty, 3> {
+                self.translation + self.transform_vector(p)
+            }
+
+            #[allow(unused)]
+            pub fn transform_vector(self, v: Vector<$fty, 3>) -> Vector<$fty, 3> {
+                self.rotation.rotate(self.scale * v)
+            }
+
+            // Exact with uniform scale, otherwise the shear a rotated
+            // non-uniform scale would need is dropped, same as in `Mul`
+            #[allow(unused)]
+            pub fn inverse(self) -> Self {
+                let rotation = self.rotation.inverse();
+                let scale = Vector([1.0; 3]) / self.scale;
+                Self {
+                    translation: scale * rotation.rotate(-self.translation),
+                    rotation,
+                    scale,
+                }
+            }
+
+            #[allow(unused)]
+            pub fn to_mat4(self) -> Matrix<$fty, 4, 4> {
+                let mut out = self.rotation.to_mat4();
+                for col in 0..3 {
+                    for row in 0..3 {
+                        out.0[col][row] *= self.scale.0[col];
+                    }
+                }
+                out.0[3][..3].copy_from_slice(&self.translation.0);
+                out
+            }
+        }
+
+        // `a * b` applies `b` first
+        impl Mul for Transform<$fty> {
+            type Output = Self;
+            fn mul(self, rhs: Self) -> Self {
+                Self {
+                    translation: self.transform_point(rhs.translation),
+                    rotation: self.rotation * rhs.rotation,
+                    scale: self.scale * rhs.scale,
+                }
+            }
         }
     };
 }
@@ -145,6 +610,14 @@ impl<T: Copy + Neg<Output = T>, const N: usize> Neg for Vector<T, N> {
     }
 }
 
+// Logical not for masks, bitwise not for integers
+impl<T: Copy + Not<Output = T>, const N: usize> Not for Vector<T, N> {
+    type Output = Self;
+    fn not(self) -> Self {
+        Self(self.0.map(|x| !x))
+    }
+}
+
 impl_scalar_op!(Add, add);
 impl_scalar_op!(Mul, mul);
 impl_scalar_op!(Sub, sub);
@@ -160,6 +633,30 @@ impl_op_assign!(SubAssign, sub_assign);
 impl_op_assign!(MulAssign, mul_assign);
 impl_op_assign!(DivAssign, div_assign);
 
+impl_bit_op!(BitAnd, bitand, BitAndAssign, bitand_assign);
+impl_bit_op!(BitOr, bitor, BitOrAssign, bitor_assign);
+impl_bit_op!(BitXor, bitxor, BitXorAssign, bitxor_assign);
+impl_bit_op!(Shl, shl, ShlAssign, shl_assign);
+impl_bit_op!(Shr, shr, ShrAssign, shr_assign);
+
+impl_swizzle!(
+    xy: 2 [0, 1] for 2, 3, 4;
+    yx: 2 [1, 0] for 2, 3, 4;
+    xz: 2 [0, 2] for 3, 4;
+    yz: 2 [1, 2] for 3, 4;
+    zx: 2 [2, 0] for 3, 4;
+    zy: 2 [2, 1] for 3, 4;
+    zw: 2 [2, 3] for 4;
+    xyz: 3 [0, 1, 2] for 3, 4;
+    xzy: 3 [0, 2, 1] for 3, 4;
+    yxz: 3 [1, 0, 2] for 3, 4;
+    yzx: 3 [1, 2, 0] for 3, 4;
+    zxy: 3 [2, 0, 1] for 3, 4;
+    zyx: 3 [2, 1, 0] for 3, 4;
+);
+
+impl_cast!(f32, f64, i32, u32, usize);
+
 impl_fty!(f32);
 impl_fty!(f64);
 
@@ -282,6 +779,173 @@ impl<T: Copy + Sub<Output = T> + Mul<Output = T>> Vector<T, 3> {
     }
 }
 
+// `a * b` rotates by `b` first
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Output = T>> Mul for Quaternion<T> {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        let [ax, ay, az, aw] = self.0 .0;
+        let [bx, by, bz, bw] = rhs.0 .0;
+        Self(Vector([
+            aw * bx + ax * bw + ay * bz - az * by,
+            aw * by - ax * bz + ay * bw + az * bx,
+            aw * bz + ax * by - ay * bx + az * bw,
+            aw * bw - ax * bx - ay * by - az * bz,
+        ]))
+    }
+}
+
+impl<T: Copy> Serialize for Quaternion<T>
+where
+    Vector<T, 4>: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Copy> Deserialize<'de> for Quaternion<T>
+where
+    Vector<T, 4>: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let v = <Vector<T, 4> as Deserialize<'de>>::deserialize(deserializer)?;
+        Ok(Self(v))
+    }
+}
+
+impl<T: Copy, const N: usize> Vector<T, N> {
+    // Components at `indices`, as a swizzle with any of them
+    #[allow(unused)]
+    pub fn swizzle<const K: usize>(&self, indices: [usize; K]) -> Vector<T, K> {
+        Vector(indices.map(|i| self.0[i]))
+    }
+
+    // Like GLSL constructors and `as`, floats are truncated towards zero
+    #[allow(unused)]
+    pub fn cast<U: Copy>(self) -> Vector<U, N>
+    where
+        T: Cast<U>,
+    {
+        Vector(self.0.map(Cast::cast))
+    }
+}
+
+impl<T: Copy> Vector<T, 2> {
+    #[allow(unused)]
+    pub fn extend(self, z: T) -> Vector<T, 3> {
+        let [x, y] = self.0;
+        Vector([x, y, z])
+    }
+}
+
+impl<T: Copy> Vector<T, 3> {
+    #[allow(unused)]
+    pub fn extend(self, w: T) -> Vector<T, 4> {
+        let [x, y, z] = self.0;
+        Vector([x, y, z, w])
+    }
+}
+
+impl<T: Copy + PartialOrd, const N: usize> Vector<T, N> {
+    #[allow(unused)]
+    pub fn min(self, rhs: Self) -> Self {
+        Self(std::array::from_fn(|i| {
+            if rhs.0[i] < self.0[i] {
+                rhs.0[i]
+            } else {
+                self.0[i]
+            }
+        }))
+    }
+
+    #[allow(unused)]
+    pub fn max(self, rhs: Self) -> Self {
+        Self(std::array::from_fn(|i| {
+            if rhs.0[i] > self.0[i] {
+                rhs.0[i]
+            } else {
+                self.0[i]
+            }
+        }))
+    }
+
+    #[allow(unused)]
+    pub fn clamp(self, lo: Self, hi: Self) -> Self {
+        self.max(lo).min(hi)
+    }
+
+    #[allow(unused)]
+    pub fn less_than(self, rhs: Self) -> Vector<bool, N> {
+        Vector(std::array::from_fn(|i| self.0[i] < rhs.0[i]))
+    }
+
+    #[allow(unused)]
+    pub fn less_than_equal(self, rhs: Self) -> Vector<bool, N> {
+        Vector(std::array::from_fn(|i| self.0[i] <= rhs.0[i]))
+    }
+
+    #[allow(unused)]
+    pub fn greater_than(self, rhs: Self) -> Vector<bool, N> {
+        Vector(std::array::from_fn(|i| self.0[i] > rhs.0[i]))
+    }
+
+    #[allow(unused)]
+    pub fn greater_than_equal(self, rhs: Self) -> Vector<bool, N> {
+        Vector(std::array::from_fn(|i| self.0[i] >= rhs.0[i]))
+    }
+
+    #[allow(unused)]
+    pub fn equal(self, rhs: Self) -> Vector<bool, N> {
+        Vector(std::array::from_fn(|i| self.0[i] == rhs.0[i]))
+    }
+
+    #[allow(unused)]
+    pub fn not_equal(self, rhs: Self) -> Vector<bool, N> {
+        Vector(std::array::from_fn(|i| self.0[i] != rhs.0[i]))
+    }
+}
+
+impl<T: Copy + PartialOrd + Default + Neg<Output = T>, const N: usize> Vector<T, N> {
+    #[allow(unused)]
+    pub fn abs(self) -> Self {
+        Self(self.0.map(|x| if x < T::default() { -x } else { x }))
+    }
+}
+
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Output = T>, const N: usize> Vector<T, N> {
+    // As `mix(self, rhs, t)` in GLSL
+    #[allow(unused)]
+    pub fn lerp(self, rhs: Self, t: T) -> Self {
+        self + (rhs - self) * t
+    }
+}
+
+impl<const N: usize> Vector<bool, N> {
+    #[allow(unused)]
+    pub fn any(self) -> bool {
+        self.0.iter().any(|&b| b)
+    }
+
+    #[allow(unused)]
+    pub fn all(self) -> bool {
+        self.0.iter().all(|&b| b)
+    }
+
+    // Components of `a` where the mask is set and of `b` elsewhere
+    #[allow(unused)]
+    pub fn select<T: Copy>(self, a: Vector<T, N>, b: Vector<T, N>) -> Vector<T, N> {
+        Vector(std::array::from_fn(
+            |i| if self.0[i] { a.0[i] } else { b.0[i] },
+        ))
+    }
+}
+
 impl<T: Copy + Default, const N: usize, const M: usize> Default for Matrix<T, N, M> {
     fn default() -> Self {
         Self([[T::default(); N]; M])
@@ -353,6 +1017,14 @@ impl<T: Copy + Default + Mul<Output = T> + AddAssign, const N: usize, const M: u
     }
 }
 
+impl<T: Copy, const N: usize, const M: usize> Matrix<T, N, M> {
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        for col in 0..M {
+            self.0[col].swap(a, b);
+        }
+    }
+}
+
 impl<T: Copy, const N: usize> Matrix<T, N, N> {
     #[allow(unused)]
     pub fn transpose_inplace(&mut self) {
@@ -364,6 +1036,32 @@ impl<T: Copy, const N: usize> Matrix<T, N, N> {
     }
 }
 
+pub trait Cast<U> {
+    fn cast(self) -> U;
+}
+
+impl<T: Copy, const N: usize, const M: usize> Matrix<T, N, M> {
+    #[allow(unused)]
+    pub fn cast<U: Copy>(self) -> Matrix<U, N, M>
+    where
+        T: Cast<U>,
+    {
+        Matrix(self.0.map(|col| col.map(Cast::cast)))
+    }
+}
+
+impl<T: Copy, const N: usize> From<[T; N]> for Vector<T, N> {
+    fn from(value: [T; N]) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: Copy, const N: usize> From<Vector<T, N>> for [T; N] {
+    fn from(value: Vector<T, N>) -> Self {
+        value.0
+    }
+}
+
 impl<T: Copy, const N: usize> From<T> for Vector<T, N> {
     fn from(value: T) -> Self {
         Self([value; N])
@@ -387,3 +1085,544 @@ impl<T: Copy, const N: usize> From<Vector<T, N>> for Matrix<T, N, 1> {
         Matrix([value.0])
     }
 }
+
+#[derive(Clone, Copy, Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct Aabb {
+    pub lo: vec3,
+    pub hi: vec3,
+}
+
+#[derive(Clone, Copy, Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct Ray {
+    pub origin: vec3,
+    pub dir: vec3,
+}
+
+// Points where `normal.dot(p) + d == 0`, with `normal` of unit length or zero.
+// Distances are positive on the side `normal` points to.
+#[derive(Clone, Copy, Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct Plane {
+    pub normal: vec3,
+    pub d: f32,
+}
+
+#[derive(Clone, Copy, Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct Sphere {
+    pub center: vec3,
+    pub radius: f32,
+}
+
+// Left, right, top, bottom, near and far planes, facing inwards
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Aabb {
+    #[allow(unused)]
+    pub fn center(&self) -> vec3 {
+        (self.lo + self.hi) * 0.5
+    }
+
+    #[allow(unused)]
+    pub fn half_size(&self) -> vec3 {
+        (self.hi - self.lo) * 0.5
+    }
+
+    #[allow(unused)]
+    pub fn contains(&self, p: vec3) -> bool {
+        (0..3).all(|i| self.lo.0[i] <= p.0[i] && p.0[i] <= self.hi.0[i])
+    }
+
+    #[allow(unused)]
+    pub fn intersects(&self, other: &Self) -> bool {
+        (0..3).all(|i| self.lo.0[i] <= other.hi.0[i] && other.lo.0[i] <= self.hi.0[i])
+    }
+}
+
+impl Sphere {
+    #[allow(unused)]
+    pub fn contains(&self, p: vec3) -> bool {
+        let d = p - self.center;
+        d.dot(d) <= self.radius * self.radius
+    }
+}
+
+impl Plane {
+    #[allow(unused)]
+    pub fn from_point_normal(p: vec3, normal: vec3) -> Self {
+        let normal = normal.normalize();
+        Self {
+            normal,
+            d: -normal.dot(p),
+        }
+    }
+
+    #[allow(unused)]
+    pub fn distance(&self, p: vec3) -> f32 {
+        self.normal.dot(p) + self.d
+    }
+
+    // From `[a, b, c, d]` of `a * x + b * y + c * z + d`, keeping degenerate planes
+    fn from_coefficients([a, b, c, d]: [f32; 4]) -> Self {
+        let normal = Vector([a, b, c]);
+        let length = normal.length();
+        if length == 0.0 {
+            return Self { normal, d };
+        }
+        Self {
+            normal: normal / length,
+            d: d / length,
+        }
+    }
+}
+
+// Intersections are ranges `[t_enter, t_exit]` of `origin + t * dir` clipped
+// to `t >= 0`, so a ray starting inside enters at 0
+impl Ray {
+    #[allow(unused)]
+    pub fn at(&self, t: f32) -> vec3 {
+        self.origin + self.dir * t
+    }
+
+    #[allow(unused)]
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<[f32; 2]> {
+        let mut t_enter = 0f32;
+        let mut t_exit = f32::INFINITY;
+        for i in 0..3 {
+            let o = self.origin.0[i];
+            let dir = self.dir.0[i];
+            if dir == 0.0 {
+                // Parallel to the slab, never crossing it
+                if o < aabb.lo.0[i] || aabb.hi.0[i] < o {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (aabb.lo.0[i] - o) / dir;
+            let t1 = (aabb.hi.0[i] - o) / dir;
+            t_enter = t_enter.max(t0.min(t1));
+            t_exit = t_exit.min(t0.max(t1));
+        }
+        (t_enter <= t_exit).then_some([t_enter, t_exit])
+    }
+
+    #[allow(unused)]
+    pub fn intersect_sphere(&self, sphere: &Sphere) -> Option<[f32; 2]> {
+        // Roots of `|origin + t * dir - center|^2 = radius^2`
+        let oc = self.origin - sphere.center;
+        let a = self.dir.dot(self.dir);
+        let half_b = oc.dot(self.dir);
+        let c = oc.dot(oc) - sphere.radius * sphere.radius;
+        let discriminant = half_b * half_b - a * c;
+        if a == 0.0 || discriminant < 0.0 {
+            return (a == 0.0 && c <= 0.0).then_some([0.0, f32::INFINITY]);
+        }
+        let sqrt = discriminant.sqrt();
+        let t_exit = (-half_b + sqrt) / a;
+        if t_exit < 0.0 {
+            return None;
+        }
+        Some([((-half_b - sqrt) / a).max(0.0), t_exit])
+    }
+
+    // Where the ray crosses the plane, `None` if it never does at `t >= 0`
+    #[allow(unused)]
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<f32> {
+        let speed = plane.normal.dot(self.dir);
+        let distance = plane.distance(self.origin);
+        if distance == 0.0 {
+            return Some(0.0);
+        }
+        let t = -distance / speed;
+        (t >= 0.0 && t.is_finite()).then_some(t)
+    }
+}
+
+impl Frustum {
+    // Planes of the clip space volume of `mat_view_proj`, with depth in [0, 1]
+    // as in Vulkan. Planes of infinite far planes come out degenerate and let
+    // everything through.
+    #[allow(unused)]
+    pub fn from_matrix(m: &mat4) -> Self {
+        let row = |i: usize| Vector([m.0[0][i], m.0[1][i], m.0[2][i], m.0[3][i]]);
+        let [x, y, z, w] = [row(0), row(1), row(2), row(3)];
+        Self {
+            planes: [w + x, w - x, w + y, w - y, z, w - z].map(|p| Plane::from_coefficients(p.0)),
+        }
+    }
+
+    // Conservative, may let through boxes near the corners which are outside
+    #[allow(unused)]
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center();
+        let half_size = aabb.half_size();
+        self.planes.iter().all(|plane| {
+            let radius: f32 = (0..3)
+                .map(|i| plane.normal.0[i].abs() * half_size.0[i])
+                .sum();
+            plane.distance(center) >= -radius
+        })
+    }
+
+    #[allow(unused)]
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.distance(sphere.center) >= -sphere.radius)
+    }
+
+    #[allow(unused)]
+    pub fn contains(&self, p: vec3) -> bool {
+        self.planes.iter().all(|plane| plane.distance(p) >= 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f32 = 1e-4;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() <= EPS * b.abs().max(1.0), "{a} != {b}");
+    }
+
+    fn assert_mat_close<const N: usize>(a: &Matrix<f32, N, N>, b: &Matrix<f32, N, N>) {
+        for col in 0..N {
+            for row in 0..N {
+                assert_close(a.0[col][row], b.0[col][row]);
+            }
+        }
+    }
+
+    // Normalized device coordinates of a point in view space
+    fn project(m: &mat4, p: vec3) -> vec3 {
+        let clip = m.dotv(p.extend(1.0));
+        Vector([clip.0[0], clip.0[1], clip.0[2]]) / clip.0[3]
+    }
+
+    #[test]
+    fn perspective_depth_range() {
+        let (fov_y, aspect, near, far) = (1.2, 1.5, 0.1, 100.0);
+        let m = mat4::perspective(fov_y, aspect, near, far);
+        let m_reversed = mat4::perspective_reversed(fov_y, aspect, near, far);
+        for (z, depth) in [(near, 0.0), (far, 1.0)] {
+            assert_close(project(&m, Vector([0.0, 0.0, z])).0[2], depth);
+            assert_close(
+                project(&m_reversed, Vector([0.0, 0.0, z])).0[2],
+                1.0 - depth,
+            );
+        }
+        let mid = project(&m, Vector([0.0, 0.0, 1.0])).0[2];
+        assert!(0.0 < mid && mid < 1.0);
+
+        // Corners of the view pyramid land on the corners of the screen
+        let half_y = (0.5 * fov_y).tan();
+        let corner = project(&m, Vector([aspect * half_y, -half_y, 1.0]) * 7.0);
+        assert_close(corner.0[0], 1.0);
+        assert_close(corner.0[1], -1.0);
+    }
+
+    #[test]
+    fn perspective_infinite_limit() {
+        let (fov_y, aspect, near) = (1.2, 1.5, 0.1);
+        let m = mat4::perspective_infinite(fov_y, aspect, near);
+        let m_reversed = mat4::perspective_infinite_reversed(fov_y, aspect, near);
+        assert_close(project(&m, Vector([0.0, 0.0, near])).0[2], 0.0);
+        assert_close(project(&m_reversed, Vector([0.0, 0.0, near])).0[2], 1.0);
+        assert_close(project(&m, Vector([0.0, 0.0, 1e6])).0[2], 1.0);
+        assert_close(project(&m_reversed, Vector([0.0, 0.0, 1e6])).0[2], 0.0);
+
+        // Finite projections approach the infinite ones as far goes to infinity
+        let far = 1e7;
+        assert_mat_close(&mat4::perspective(fov_y, aspect, near, far), &m);
+        assert_mat_close(
+            &mat4::perspective_reversed(fov_y, aspect, near, far),
+            &m_reversed,
+        );
+    }
+
+    #[test]
+    fn look_at_basis() {
+        let eye = Vector([1.0, 2.0, 3.0]);
+        let target = Vector([4.0, -2.0, 3.0]);
+        let up = Vector([0.0, 0.0, 1.0]);
+        let m = mat4::look_at(eye, target, up);
+        let row = |i: usize| Vector([m.0[0][i], m.0[1][i], m.0[2][i]]);
+        let (right, down, forward) = (row(0), row(1), row(2));
+
+        for (i, a) in [right, down, forward].into_iter().enumerate() {
+            for (j, b) in [right, down, forward].into_iter().enumerate() {
+                assert_close(a.dot(b), if i == j { 1.0 } else { 0.0 });
+            }
+        }
+        assert_close(m.determinant(), 1.0);
+        assert_close(forward.dot(Vector([0.6, -0.8, 0.0])), 1.0);
+        assert_close(down.dot(up), -1.0);
+
+        let view = |p: vec3| {
+            let p = m.dotv(p.extend(1.0));
+            Vector([p.0[0], p.0[1], p.0[2]])
+        };
+        assert_close(view(eye).length(), 0.0);
+        let target_view = view(target);
+        assert_close(target_view.0[0], 0.0);
+        assert_close(target_view.0[1], 0.0);
+        assert_close(target_view.0[2], 5.0);
+        assert!(view(eye + up).0[1] < 0.0);
+    }
+
+    #[test]
+    fn inverse_times_matrix_is_identity() {
+        let eye = Vector([1.0, 2.0, 3.0]);
+        let view = mat4::look_at(eye, Vector([-2.0, 0.5, 7.0]), Vector([0.0, -1.0, 0.0]));
+        let matrices = [
+            mat4::identity(),
+            view,
+            mat4::perspective(1.2, 1.5, 0.1, 100.0).dot(&view),
+            mat4::orthographic(Vector([-3.0, -2.0, 0.5]), Vector([5.0, 4.0, 20.0])),
+            Matrix([
+                [0.0, 2.0, 1.0, 3.0],
+                [1.0, 0.0, 4.0, -1.0],
+                [2.0, 1.0, 0.0, 5.0],
+                [-3.0, 1.0, 2.0, 1.0],
+            ]),
+        ];
+        for m in matrices {
+            let inverse = m.inverse().unwrap();
+            assert_mat_close(&inverse.dot(&m), &mat4::identity());
+            assert_mat_close(&m.dot(&inverse), &mat4::identity());
+        }
+    }
+
+    #[test]
+    fn known_determinants() {
+        assert_close(mat4::identity().determinant(), 1.0);
+        let diagonal = Matrix::<f32, 4, 4>([
+            [2.0, 0.0, 0.0, 0.0],
+            [0.0, 3.0, 0.0, 0.0],
+            [0.0, 0.0, 4.0, 0.0],
+            [0.0, 0.0, 0.0, 5.0],
+        ]);
+        assert_close(diagonal.determinant(), 120.0);
+        let swapped = Matrix::<f32, 4, 4>([
+            [0.0, 1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert_close(swapped.determinant(), -1.0);
+        let m = Matrix::<f32, 3, 3>([[6.0, 4.0, 2.0], [1.0, -2.0, 8.0], [1.0, 5.0, 7.0]]);
+        assert_close(m.determinant(), -306.0);
+
+        // Third column is the sum of the first two
+        let singular = Matrix::<f32, 3, 3>([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [5.0, 7.0, 9.0]]);
+        assert_close(singular.determinant(), 0.0);
+        let singular = Matrix::<f32, 3, 3>([[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 1.0, 1.0]]);
+        assert_eq!(singular.determinant(), 0.0);
+        assert!(singular.inverse().is_none());
+    }
+
+    fn ray(origin: [f32; 3], dir: [f32; 3]) -> Ray {
+        Ray {
+            origin: Vector(origin),
+            dir: Vector(dir),
+        }
+    }
+
+    fn assert_range(range: Option<[f32; 2]>, expected: [f32; 2]) {
+        let range = range.unwrap();
+        assert_close(range[0], expected[0]);
+        assert_close(range[1], expected[1]);
+    }
+
+    #[test]
+    fn ray_aabb() {
+        let aabb = Aabb {
+            lo: Vector([0.0; 3]),
+            hi: Vector([1.0; 3]),
+        };
+        assert_range(
+            ray([-1.0, 0.5, 0.5], [1.0, 0.0, 0.0]).intersect_aabb(&aabb),
+            [1.0, 2.0],
+        );
+        assert_range(
+            ray([2.0, 2.0, 0.5], [-1.0, -1.0, 0.0]).intersect_aabb(&aabb),
+            [1.0, 2.0],
+        );
+        assert!(ray([2.0, 0.5, 0.5], [1.0, 0.0, 0.0])
+            .intersect_aabb(&aabb)
+            .is_none());
+        assert!(ray([-1.0, 0.0, 0.5], [1.0, 2.0, 0.0])
+            .intersect_aabb(&aabb)
+            .is_none());
+
+        // Parallel to the y and z slabs, inside them or on their boundary
+        assert_range(
+            ray([-1.0, 1.0, 0.0], [2.0, 0.0, 0.0]).intersect_aabb(&aabb),
+            [0.5, 1.0],
+        );
+        assert!(ray([-1.0, 1.5, 0.5], [1.0, 0.0, 0.0])
+            .intersect_aabb(&aabb)
+            .is_none());
+        assert!(ray([-1.0, 0.5, -0.5], [1.0, 0.0, 0.0])
+            .intersect_aabb(&aabb)
+            .is_none());
+
+        // Starting inside enters at 0
+        assert_range(
+            ray([0.5, 0.5, 0.5], [0.0, 0.0, 2.0]).intersect_aabb(&aabb),
+            [0.0, 0.25],
+        );
+        assert_eq!(
+            ray([0.5; 3], [0.0; 3]).intersect_aabb(&aabb),
+            Some([0.0, f32::INFINITY])
+        );
+        assert!(ray([1.5; 3], [0.0; 3]).intersect_aabb(&aabb).is_none());
+    }
+
+    #[test]
+    fn ray_sphere() {
+        let sphere = Sphere {
+            center: Vector([0.0, 0.0, 5.0]),
+            radius: 1.0,
+        };
+        assert_range(
+            ray([0.0; 3], [0.0, 0.0, 1.0]).intersect_sphere(&sphere),
+            [4.0, 6.0],
+        );
+        assert_range(
+            ray([0.0; 3], [0.0, 0.0, 2.0]).intersect_sphere(&sphere),
+            [2.0, 3.0],
+        );
+        assert!(ray([0.0, 2.0, 0.0], [0.0, 0.0, 1.0])
+            .intersect_sphere(&sphere)
+            .is_none());
+        assert!(ray([0.0; 3], [0.0, 0.0, -1.0])
+            .intersect_sphere(&sphere)
+            .is_none());
+        assert_range(
+            ray([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]).intersect_sphere(&sphere),
+            [5.0, 5.0],
+        );
+
+        // Starting inside enters at 0
+        assert_range(
+            ray([0.0, 0.0, 5.0], [0.0, 0.0, 2.0]).intersect_sphere(&sphere),
+            [0.0, 0.5],
+        );
+        assert_eq!(
+            ray([0.0, 0.5, 5.0], [0.0; 3]).intersect_sphere(&sphere),
+            Some([0.0, f32::INFINITY]),
+        );
+        assert!(ray([0.0; 3], [0.0; 3]).intersect_sphere(&sphere).is_none());
+    }
+
+    #[test]
+    fn ray_plane() {
+        let plane = Plane::from_point_normal(Vector([0.0, 0.0, 2.0]), Vector([0.0, 0.0, 3.0]));
+        assert_close(
+            ray([0.0; 3], [0.0, 0.0, 4.0])
+                .intersect_plane(&plane)
+                .unwrap(),
+            0.5,
+        );
+        assert_close(
+            ray([1.0, 1.0, 3.0], [0.0, 0.0, -1.0])
+                .intersect_plane(&plane)
+                .unwrap(),
+            1.0,
+        );
+        assert!(ray([0.0; 3], [0.0, 0.0, -1.0])
+            .intersect_plane(&plane)
+            .is_none());
+
+        // Parallel rays cross only if they start on the plane
+        assert!(ray([0.0; 3], [1.0, 0.0, 0.0])
+            .intersect_plane(&plane)
+            .is_none());
+        assert_eq!(
+            ray([0.0, 0.0, 2.0], [1.0, 0.0, 0.0]).intersect_plane(&plane),
+            Some(0.0)
+        );
+        assert_eq!(
+            ray([5.0, 0.0, 2.0], [0.0, 0.0, 1.0]).intersect_plane(&plane),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn frustum_of_clip_space() {
+        let frustum = Frustum::from_matrix(&mat4::identity());
+        for plane in frustum.planes {
+            assert_close(plane.normal.length(), 1.0);
+        }
+        assert!(frustum.contains(Vector([0.0, 0.0, 0.5])));
+        assert!(frustum.contains(Vector([1.0, -1.0, 1.0])));
+        for p in [
+            [1.5, 0.0, 0.5],
+            [0.0, -1.5, 0.5],
+            [0.0, 0.0, -0.1],
+            [0.0, 0.0, 1.1],
+        ] {
+            assert!(!frustum.contains(Vector(p)), "{p:?}");
+        }
+
+        let aabb = |lo: [f32; 3], hi: [f32; 3]| Aabb {
+            lo: Vector(lo),
+            hi: Vector(hi),
+        };
+        assert!(frustum.intersects_aabb(&aabb([-0.1; 3], [0.1; 3])));
+        assert!(frustum.intersects_aabb(&aabb([0.5, 0.0, 0.5], [1.5, 0.1, 0.6])));
+        assert!(frustum.intersects_aabb(&aabb([-5.0; 3], [5.0; 3])));
+        assert!(!frustum.intersects_aabb(&aabb([1.5, 0.0, 0.5], [2.5, 0.1, 0.6])));
+        assert!(!frustum.intersects_aabb(&aabb([0.0, 0.0, 1.5], [0.1, 0.1, 2.5])));
+
+        let sphere = |center: [f32; 3], radius: f32| Sphere {
+            center: Vector(center),
+            radius,
+        };
+        assert!(frustum.intersects_sphere(&sphere([0.0, 0.0, 0.5], 0.1)));
+        assert!(frustum.intersects_sphere(&sphere([1.5, 0.0, 0.5], 0.6)));
+        assert!(!frustum.intersects_sphere(&sphere([1.5, 0.0, 0.5], 0.4)));
+        assert!(!frustum.intersects_sphere(&sphere([0.0, 0.0, -1.0], 0.5)));
+    }
+
+    #[test]
+    fn frustum_of_camera() {
+        let eye = Vector([1.0, 2.0, 3.0]);
+        let forward = Vector([0.6, -0.8, 0.0]);
+        let up = Vector([0.0, 0.0, 1.0]);
+        let view = mat4::look_at(eye, eye + forward * 5.0, up);
+        let frustum = Frustum::from_matrix(&mat4::perspective(1.2, 1.5, 0.1, 100.0).dot(&view));
+        let sphere = |center: vec3, radius: f32| Sphere { center, radius };
+
+        let ahead = eye + forward * 10.0;
+        assert!(frustum.contains(ahead));
+        assert!(frustum.intersects_sphere(&sphere(ahead, 0.1)));
+        assert!(frustum.intersects_aabb(&Aabb {
+            lo: ahead - 0.1,
+            hi: ahead + 0.1,
+        }));
+
+        // Behind the camera, beyond the far plane and above the view
+        for p in [
+            eye - forward * 10.0,
+            eye + forward * 150.0,
+            ahead + up * 20.0,
+        ] {
+            assert!(!frustum.contains(p), "{p:?}");
+            assert!(!frustum.intersects_sphere(&sphere(p, 1.0)), "{p:?}");
+            assert!(
+                !frustum.intersects_aabb(&Aabb {
+                    lo: p - 1.0,
+                    hi: p + 1.0,
+                }),
+                "{p:?}",
+            );
+        }
+        assert!(!frustum.contains(eye + forward * 0.05));
+        assert!(frustum.intersects_sphere(&sphere(eye + forward * 100.5, 1.0)));
+    }
+}
@@ -13,11 +13,15 @@ use crate::{
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct State {
     pub orbit_center: vec3,
     pub orbit_distance: vec2,
     pub angle_deg: f32,
     pub turn_speed: f32,
+    pub fov_deg: f32,
+    pub z_near: f32,
+    pub z_far: f32,
 
     pub particle_count: u32,
     pub time_scale: f32,
@@ -27,6 +31,9 @@ pub struct State {
     pub accel: vec4,
 
     pub blur_radius: u32,
+
+    pub octree_origin: vec3,
+    pub voxel_size: f32,
 }
 
 #[derive(Debug)]
@@ -42,6 +49,9 @@ impl Default for State {
             orbit_distance: Vector([1.0; 2]),
             angle_deg: 0.0,
             turn_speed: 0.0,
+            fov_deg: 90.0,
+            z_near: 0.01,
+            z_far: 100.0,
 
             particle_count: MAX_PARTICLE_COUNT as _,
             time_scale: 1.0,
@@ -51,6 +61,9 @@ impl Default for State {
             accel: Vector([0.0; 4]),
 
             blur_radius: 0,
+
+            octree_origin: Vector([-0.5; 3]),
+            voxel_size: 1.0 / 64.0,
         }
     }
 }
@@ -1,4 +1,5 @@
 use crate::{
+    layout::{GpuLayout, Std140},
     vklib::{vkbox, CommittedBuffer, CommittedImage, VkContext},
     CameraData, SimulationStepParams, MAX_CONCURRENT_FRAMES,
 };
@@ -21,7 +22,7 @@ pub unsafe fn create_descriptor_sets_simulation(
         let uniform_buffer_info = [vk::DescriptorBufferInfo {
             buffer: params_buffers[i].buffer.0,
             offset: 0,
-            range: std::mem::size_of::<SimulationStepParams>() as _,
+            range: <SimulationStepParams as GpuLayout<Std140>>::SIZE as _,
         }];
         let storage_buffer_info = [vk::DescriptorBufferInfo {
             buffer: particles_buffer,
@@ -62,7 +63,7 @@ pub unsafe fn create_descriptor_sets_main(
         let uniform_buffer_info = [vk::DescriptorBufferInfo {
             buffer: camera_buffers[i].buffer.0,
             offset: 0,
-            range: std::mem::size_of::<CameraData>() as _,
+            range: <CameraData as GpuLayout<Std140>>::SIZE as _,
         }];
         let descriptor_writes = [vk::WriteDescriptorSet::default()
             .dst_set(sets[i])
@@ -75,6 +76,119 @@ pub unsafe fn create_descriptor_sets_main(
     sets
 }
 
+// Sets of the mesh pipeline, voxel colours come from the palette
+#[allow(unused)]
+pub unsafe fn create_descriptor_sets_mesh(
+    vk: &VkContext,
+    descriptor_pool: vk::DescriptorPool,
+    layout: vk::DescriptorSetLayout,
+    camera_buffers: &[CommittedBuffer],
+    palette_buffers: &[CommittedBuffer],
+) -> Vec<vk::DescriptorSet> {
+    let set_layouts = [layout; MAX_CONCURRENT_FRAMES];
+    let allocate_info = vk::DescriptorSetAllocateInfo::default()
+        .descriptor_pool(descriptor_pool)
+        .set_layouts(&set_layouts);
+    let sets = vk.device.allocate_descriptor_sets(&allocate_info).unwrap();
+    for i in 0..MAX_CONCURRENT_FRAMES {
+        let uniform_buffer_info = [vk::DescriptorBufferInfo {
+            buffer: camera_buffers[i].buffer.0,
+            offset: 0,
+            range: <CameraData as GpuLayout<Std140>>::SIZE as _,
+        }];
+        let palette_buffer_info = [vk::DescriptorBufferInfo {
+            buffer: palette_buffers[i].buffer.0,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+        }];
+        let descriptor_writes = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(sets[i])
+                .dst_binding(0)
+                .descriptor_count(1)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .buffer_info(&uniform_buffer_info),
+            vk::WriteDescriptorSet::default()
+                .dst_set(sets[i])
+                .dst_binding(1)
+                .descriptor_count(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&palette_buffer_info),
+        ];
+        vk.device.update_descriptor_sets(&descriptor_writes, &[]);
+    }
+    sets
+}
+
+pub unsafe fn create_descriptor_sets_octree(
+    vk: &VkContext,
+    descriptor_pool: vk::DescriptorPool,
+    layout: vk::DescriptorSetLayout,
+    camera_buffers: &[CommittedBuffer],
+    octree_buffer: vk::Buffer,
+    palette_buffers: &[CommittedBuffer],
+) -> Vec<vk::DescriptorSet> {
+    let set_layouts = [layout; MAX_CONCURRENT_FRAMES];
+    let allocate_info = vk::DescriptorSetAllocateInfo::default()
+        .descriptor_pool(descriptor_pool)
+        .set_layouts(&set_layouts);
+    let sets = vk.device.allocate_descriptor_sets(&allocate_info).unwrap();
+    for i in 0..MAX_CONCURRENT_FRAMES {
+        let uniform_buffer_info = [vk::DescriptorBufferInfo {
+            buffer: camera_buffers[i].buffer.0,
+            offset: 0,
+            range: <CameraData as GpuLayout<Std140>>::SIZE as _,
+        }];
+        let palette_buffer_info = [vk::DescriptorBufferInfo {
+            buffer: palette_buffers[i].buffer.0,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+        }];
+        let descriptor_writes = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(sets[i])
+                .dst_binding(0)
+                .descriptor_count(1)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .buffer_info(&uniform_buffer_info),
+            vk::WriteDescriptorSet::default()
+                .dst_set(sets[i])
+                .dst_binding(2)
+                .descriptor_count(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&palette_buffer_info),
+        ];
+        vk.device.update_descriptor_sets(&descriptor_writes, &[]);
+    }
+    update_descriptor_sets_octree(vk, &sets, octree_buffer);
+    sets
+}
+
+// Points the sets at another node pool, none of them may be in use
+pub unsafe fn update_descriptor_sets_octree(
+    vk: &VkContext,
+    descriptor_sets: &[vk::DescriptorSet],
+    octree_buffer: vk::Buffer,
+) {
+    let storage_buffer_info = [vk::DescriptorBufferInfo {
+        buffer: octree_buffer,
+        offset: 0,
+        range: vk::WHOLE_SIZE,
+    }];
+    let descriptor_writes: Vec<_> = descriptor_sets
+        .iter()
+        .map(|&set| {
+            vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(1)
+                .descriptor_count(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&storage_buffer_info)
+        })
+        .collect();
+    vk.device.update_descriptor_sets(&descriptor_writes, &[]);
+}
+
 pub unsafe fn create_descriptor_sets_filter(
     vk: &VkContext,
     descriptor_pool: vk::DescriptorPool,
@@ -1,14 +1,19 @@
 mod descriptor_pool;
 mod descriptor_sets;
+mod octree_pool;
 mod pipelines;
 mod render_passes;
 mod swapchain;
 
 pub use descriptor_pool::create_descriptor_pool;
+#[allow(unused)]
+pub use descriptor_sets::create_descriptor_sets_mesh;
 pub use descriptor_sets::{
-    create_descriptor_sets_filter, create_descriptor_sets_main,
+    create_descriptor_sets_filter, create_descriptor_sets_main, create_descriptor_sets_octree,
     create_descriptor_sets_simulation, update_descriptor_sets_filter,
+    update_descriptor_sets_octree,
 };
+pub use octree_pool::OctreeNodePool;
 pub use pipelines::{PipelineBox, PipelineVec};
 pub use render_passes::create_render_pass;
 pub use swapchain::Swapchain;
@@ -0,0 +1,248 @@
+use crate::{
+    vklib::{CommittedBuffer, VkContext},
+    voxel::octree::Octree,
+    MAX_CONCURRENT_FRAMES,
+};
+use ash::vk;
+use std::{collections::VecDeque, ffi::c_void, mem, ops::Range, ptr};
+
+const HEADER_SIZE: usize = 4 * mem::size_of::<u32>();
+const NODE_SIZE: usize = 12 * mem::size_of::<u32>();
+// Dirty ranges closer than this many nodes are copied as one region
+const MAX_GAP: usize = 4;
+
+// Device-local copy of `Octree::gpu_data()` which keeps the node indices of
+// the tree, so nodes freed and reused by edits are freed and reused on the
+// GPU as well. Changed nodes are streamed through a host-visible ring with a
+// segment per frame in flight, whatever does not fit waits for later frames.
+//
+// Nodes are kept in two halves, the header points the shader at one of them
+// while the other one is being brought up to date. A half is shown only once
+// whole batches of edits are copied into it, so edits are never seen half-done.
+#[derive(Debug)]
+pub struct OctreeNodePool<'a> {
+    pub buffer: CommittedBuffer<'a>,
+    capacity: usize,
+    staging: CommittedBuffer<'a>,
+    staging_mapping: *mut c_void,
+    segment_size: usize,
+    header: [u32; 4],
+    front: usize,
+    batches: VecDeque<Batch>,
+    // Number of batches dropped from the front of `batches` so far
+    first_batch: usize,
+    // Batches copied into each half, and nodes copied of the next batch
+    progress: [(usize, usize); 2],
+}
+
+// Nodes changed between two calls to `queue`, as they were at the second one
+#[derive(Debug)]
+struct Batch {
+    header: [u32; 4],
+    ranges: Vec<Range<usize>>,
+    nodes: Vec<[u32; 12]>,
+}
+
+impl<'a> OctreeNodePool<'a> {
+    pub unsafe fn new(
+        vk: &'a VkContext,
+        command_pool: vk::CommandPool,
+        tree: &mut Octree,
+        capacity: usize,
+        staging_size: usize,
+    ) -> Self {
+        assert!(tree.capacity() <= capacity);
+        let segment_size = staging_size / MAX_CONCURRENT_FRAMES;
+        assert!(segment_size >= HEADER_SIZE + NODE_SIZE);
+
+        let header = tree.gpu_header();
+        let mut data = vec![0; (HEADER_SIZE + 2 * NODE_SIZE * capacity) / mem::size_of::<u32>()];
+        data[..4].copy_from_slice(&header);
+        for half in 0..2 {
+            for i_node in 0..tree.capacity() {
+                data[4 + 12 * (half * capacity + i_node)..][..12]
+                    .copy_from_slice(&tree.gpu_node(i_node));
+            }
+        }
+        tree.take_dirty_ranges(0);
+        let buffer = CommittedBuffer::upload(
+            vk,
+            command_pool,
+            &data,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        );
+
+        let staging = CommittedBuffer::new(
+            vk,
+            staging_size as _,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let staging_mapping = vk
+            .device
+            .map_memory(
+                staging.memory.0,
+                0,
+                staging_size as _,
+                vk::MemoryMapFlags::empty(),
+            )
+            .unwrap();
+        Self {
+            buffer,
+            capacity,
+            staging,
+            staging_mapping,
+            segment_size,
+            header,
+            front: 0,
+            batches: VecDeque::new(),
+            first_batch: 0,
+            progress: [(0, 0); 2],
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Takes the nodes changed by edits since the previous call as a batch.
+    // Returns `false` if the tree has outgrown the pool, which then has to be
+    // created anew; the same goes for trees replaced as a whole or shrinked.
+    pub fn queue(&mut self, tree: &mut Octree) -> bool {
+        if tree.capacity() > self.capacity {
+            return false;
+        }
+        let ranges = tree.take_dirty_ranges(MAX_GAP);
+        let header = tree.gpu_header();
+        if ranges.is_empty() && header == self.header {
+            return true;
+        }
+        let nodes = ranges
+            .iter()
+            .flat_map(|range| range.clone())
+            .map(|i_node| tree.gpu_node(i_node))
+            .collect();
+        self.batches.push_back(Batch {
+            header,
+            ranges,
+            nodes,
+        });
+        self.header = header;
+        true
+    }
+
+    // Records copies of as many queued nodes into the hidden half as fit in
+    // the staging segment of the frame, and shows the half as soon as a batch
+    // is complete. Has to be called outside of a render pass once the frame's
+    // previous submission is done.
+    pub unsafe fn record(
+        &mut self,
+        vk: &VkContext,
+        command_buffer: vk::CommandBuffer,
+        frame_in_flight_index: usize,
+    ) {
+        let back = 1 - self.front;
+        let segment_offset = frame_in_flight_index * self.segment_size;
+        // Room for the header is always left
+        let max_nodes = (self.segment_size - HEADER_SIZE) / NODE_SIZE;
+        let mut staged: Vec<u32> = Vec::new();
+        let mut regions = Vec::new();
+        let (mut i_batch, mut done) = self.progress[back];
+        while i_batch < self.first_batch + self.batches.len() {
+            let batch = &self.batches[i_batch - self.first_batch];
+            let mut skip = done;
+            for range in &batch.ranges {
+                if skip >= range.len() {
+                    skip -= range.len();
+                    continue;
+                }
+                let fit = (max_nodes - staged.len() / 12).min(range.len() - skip);
+                if fit == 0 {
+                    break;
+                }
+                let start = range.start + skip;
+                regions.push(vk::BufferCopy {
+                    src_offset: (segment_offset + mem::size_of_val(&staged[..])) as _,
+                    dst_offset: (HEADER_SIZE + NODE_SIZE * (back * self.capacity + start)) as _,
+                    size: (NODE_SIZE * fit) as _,
+                });
+                for node in &batch.nodes[done..done + fit] {
+                    staged.extend_from_slice(node);
+                }
+                done += fit;
+                skip = 0;
+            }
+            if done < batch.nodes.len() {
+                break;
+            }
+            i_batch += 1;
+            done = 0;
+            // Shown right away, later batches are left for the next frames
+            if i_batch > self.progress[self.front].0 {
+                break;
+            }
+        }
+        self.progress[back] = (i_batch, done);
+
+        if i_batch > self.progress[self.front].0 && done == 0 {
+            let mut header = self.batches[i_batch - 1 - self.first_batch].header;
+            header[2] = (back * self.capacity) as _;
+            regions.push(vk::BufferCopy {
+                src_offset: (segment_offset + mem::size_of_val(&staged[..])) as _,
+                dst_offset: 0,
+                size: HEADER_SIZE as _,
+            });
+            staged.extend_from_slice(&header);
+            self.front = back;
+        }
+        while self.first_batch < self.progress[0].0.min(self.progress[1].0) {
+            self.batches.pop_front();
+            self.first_batch += 1;
+        }
+        if regions.is_empty() {
+            return;
+        }
+        ptr::copy_nonoverlapping(
+            staged.as_ptr() as *const u8,
+            (self.staging_mapping as *mut u8).add(segment_offset),
+            mem::size_of_val(&staged[..]),
+        );
+
+        // Previous frames may still be reading the half being overwritten
+        let barriers = [vk::BufferMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::SHADER_READ)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(self.buffer.buffer.0)
+            .offset(0)
+            .size(vk::WHOLE_SIZE)];
+        vk.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &barriers,
+            &[],
+        );
+        vk.device.cmd_copy_buffer(
+            command_buffer,
+            self.staging.buffer.0,
+            self.buffer.buffer.0,
+            &regions,
+        );
+        let barriers = [barriers[0]
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)];
+        vk.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &barriers,
+            &[],
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
+    layout::{GpuLayout, Std430},
     vklib::{vkbox, VkContext},
-    Particle, Vertex,
+    FilterParams, OctreeParams, Particle, Vertex,
 };
 use ash::vk;
 use std::{array, ffi::CStr, mem, slice};
@@ -18,6 +19,7 @@ const BYTECODE_FILTER_FRAG: [&[u8]; 4] = [
     include_bytes!(concat!(env!("OUT_DIR"), "/filter2.frag.spv")),
     include_bytes!(concat!(env!("OUT_DIR"), "/filter3.frag.spv")),
 ];
+const BYTECODE_OCTREE_FRAG: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/octree.frag.spv"));
 const BYTECODE_SIMULATION: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/simulation.comp.spv"));
 
 pub struct PipelineBox<'a> {
@@ -69,6 +71,12 @@ impl<'a> PipelineBox<'a> {
                 format: vk::Format::R32G32B32_SFLOAT,
                 offset: mem::offset_of!(Vertex, norm) as _,
             },
+            vk::VertexInputAttributeDescription {
+                location: 2,
+                binding: 0,
+                format: vk::Format::R32_UINT,
+                offset: mem::offset_of!(Vertex, voxel) as _,
+            },
         ];
         let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default()
             .vertex_binding_descriptions(&vertex_binding_descriptions)
@@ -113,11 +121,18 @@ impl<'a> PipelineBox<'a> {
         let dynamic_state_create_info =
             vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
 
-        let bindings = [vk::DescriptorSetLayoutBinding::default()
-            .binding(0)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-            .descriptor_count(1)
-            .stage_flags(vk::ShaderStageFlags::VERTEX)];
+        let bindings = [
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::VERTEX),
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        ];
         let descriptor_set_layout_create_info =
             vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
         let descriptor_set_layout =
@@ -311,6 +326,122 @@ impl<'a> PipelineBox<'a> {
             descriptor_set_layout,
         }
     }
+    pub unsafe fn new_octree(
+        vk: &'a VkContext,
+        render_pass: vk::RenderPass,
+        rasterization_samples: vk::SampleCountFlags,
+    ) -> Self {
+        let shader_module_filter_vert = vk.create_shader_module(BYTECODE_FILTER_VERT);
+        let shader_module_octree_frag = vk.create_shader_module(BYTECODE_OCTREE_FRAG);
+
+        let stage_create_infos = [
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(shader_module_filter_vert.0)
+                .name(CStr::from_bytes_with_nul(b"main\0").unwrap()),
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(shader_module_octree_frag.0)
+                .name(CStr::from_bytes_with_nul(b"main\0").unwrap()),
+        ];
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&[])
+            .vertex_attribute_descriptions(&[]);
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+        let viewports = [vk::Viewport::default()];
+        let scissors = [vk::Rect2D::default()];
+        let viewport_state = vk::PipelineViewportStateCreateInfo::default()
+            .viewports(&viewports)
+            .scissors(&scissors);
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .line_width(1.0);
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(rasterization_samples);
+        let color_blend_attachments = [vk::PipelineColorBlendAttachmentState {
+            blend_enable: vk::TRUE,
+            src_color_blend_factor: vk::BlendFactor::ONE,
+            dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: vk::ColorComponentFlags::A
+                | vk::ColorComponentFlags::B
+                | vk::ColorComponentFlags::G
+                | vk::ColorComponentFlags::R,
+        }];
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::default()
+            .flags(vk::PipelineDepthStencilStateCreateFlags::empty())
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(vk::CompareOp::LESS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false);
+        let color_blend_state =
+            vk::PipelineColorBlendStateCreateInfo::default().attachments(&color_blend_attachments);
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state_create_info =
+            vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+        let bindings = [
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(2)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        ];
+        let descriptor_set_layout_create_info =
+            vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+        let descriptor_set_layout =
+            vkbox::DescriptorSetLayout::new(vk, &descriptor_set_layout_create_info);
+
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: <OctreeParams as GpuLayout<Std430>>::SIZE as _,
+        }];
+
+        let layout_create_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(slice::from_ref(&descriptor_set_layout.0))
+            .push_constant_ranges(&push_constant_ranges);
+        let layout = vkbox::PipelineLayout::new(vk, &layout_create_info);
+        let pipeline_create_infos = [vk::GraphicsPipelineCreateInfo::default()
+            .stages(&stage_create_infos)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state_create_info)
+            .layout(layout.0)
+            .render_pass(render_pass)
+            .subpass(0)];
+        let pipelines = vk
+            .device
+            .create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_create_infos, None)
+            .unwrap();
+
+        Self {
+            pipeline: vkbox::Pipeline::wrap(vk, pipelines[0]),
+            layout,
+            descriptor_set_layout,
+        }
+    }
 }
 
 impl<'a> PipelineVec<'a> {
@@ -383,7 +514,7 @@ impl<'a> PipelineVec<'a> {
         let push_constant_ranges = [vk::PushConstantRange {
             stage_flags: vk::ShaderStageFlags::FRAGMENT,
             offset: 0,
-            size: 32,
+            size: <FilterParams as GpuLayout<Std430>>::SIZE as _,
         }];
 
         let layout_create_info = vk::PipelineLayoutCreateInfo::default()
@@ -1,12 +1,33 @@
 #version 450
 
+// Layout of Palette::gpu_data(), voxels without a material get a hashed colour
+struct Material {
+    vec4 color; // rgb --- diffuse colour, a --- opacity
+    vec4 params; // x --- emissive strength, y --- roughness
+};
+
+layout(std430, binding = 1) readonly buffer PaletteSSBO {
+    uint material_count;
+    Material materials[];
+};
+
 layout(location = 0) in vec3 in_norm;
+layout(location = 1) flat in uint in_voxel;
 layout(location = 0) out vec4 out_color;
 
+const vec3 LIGHT_DIR = normalize(vec3(0.3, -1, 0.5));
+
 void main() {
-    float factor = gl_FragCoord.z / gl_FragCoord.w;
-    if (gl_FrontFacing) {
-        factor *= -1;
+    vec3 albedo = 0.2 + 0.8 * fract(float(in_voxel) * vec3(0.618034, 0.414214, 0.732051) + 0.25);
+    float emissive = 0;
+    if (in_voxel < material_count) {
+        albedo = materials[in_voxel].color.rgb;
+        emissive = materials[in_voxel].params.x;
+    }
+    vec3 norm = normalize(in_norm);
+    if (!gl_FrontFacing) {
+        norm = -norm;
     }
-    out_color = vec4(vec3(factor), 0);
+    float diffuse = max(dot(norm, -LIGHT_DIR), 0);
+    out_color = vec4(albedo * (0.2 + 0.8 * diffuse + emissive), 1);
 }
@@ -8,9 +8,12 @@ layout(binding = 0, std140) uniform CameraBuffer {
 
 layout(location = 0) in vec3 in_pos;
 layout(location = 1) in vec3 in_norm;
+layout(location = 2) in uint in_voxel;
 layout(location = 0) out vec3 out_norm;
+layout(location = 1) flat out uint out_voxel;
 
 void main() {
     gl_Position = cam.mat_view_proj * vec4(in_pos, 1);
     out_norm = in_norm;
+    out_voxel = in_voxel;
 }
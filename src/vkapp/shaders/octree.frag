@@ -0,0 +1,123 @@
+#version 450
+
+layout(binding = 0, std140) uniform CameraBuffer {
+    mat4 mat_view;
+    mat4 mat_proj;
+    mat4 mat_view_proj;
+} cam;
+
+// Layout of OctreeNodePool: [0] --- log extent, [1] --- root, [2] --- first node of the
+// half in use, then 12 words per node starting at 4: [0] --- voxel, [4..12] --- children,
+// ~0 for leaves
+layout(std430, binding = 1) readonly buffer OctreeSSBO {
+    uint octree[];
+};
+
+// Layout of Palette::gpu_data(), voxels without a material get a hashed colour
+struct Material {
+    vec4 color; // rgb --- diffuse colour, a --- opacity
+    vec4 params; // x --- emissive strength, y --- roughness
+};
+
+layout(std430, binding = 2) readonly buffer PaletteSSBO {
+    uint material_count;
+    Material materials[];
+};
+
+layout(push_constant, std430) uniform Params {
+    vec4 transform; // xyz --- world position of the octree origin, w --- voxel size
+} params;
+
+layout(location = 0) in vec2 in_tex_coord;
+layout(location = 0) out vec4 out_color;
+
+const uint EMPTY = 0xFFFFFFFF;
+const int MAX_STEPS = 512;
+const float STEP_EPSILON = 1e-3;
+const vec3 LIGHT_DIR = normalize(vec3(0.3, -1, 0.5));
+
+uint node_voxel(uint i_node) {
+    return octree[4 + 12 * (octree[2] + i_node)];
+}
+
+uint node_child(uint i_node, uint i_child) {
+    return octree[8 + 12 * (octree[2] + i_node) + i_child];
+}
+
+vec2 intersect_box(vec3 origin, vec3 inv_dir, vec3 lo, vec3 hi) {
+    vec3 t0 = (lo - origin) * inv_dir;
+    vec3 t1 = (hi - origin) * inv_dir;
+    vec3 t_near = min(t0, t1);
+    vec3 t_far = max(t0, t1);
+    return vec2(max(max(t_near.x, t_near.y), t_near.z), min(min(t_far.x, t_far.y), t_far.z));
+}
+
+void main() {
+    vec2 ndc = 2 * in_tex_coord - 1;
+    vec3 cam_pos = inverse(cam.mat_view)[3].xyz;
+    vec4 target = inverse(cam.mat_view_proj) * vec4(ndc, 0.5, 1);
+    vec3 dir = normalize(target.xyz / target.w - cam_pos);
+    dir += 1e-8 * vec3(equal(dir, vec3(0)));
+    vec3 inv_dir = 1 / dir;
+    vec3 origin = (cam_pos - params.transform.xyz) / params.transform.w;
+
+    float extent = float(1u << octree[0]);
+    vec2 t_root = intersect_box(origin, inv_dir, vec3(0), vec3(extent));
+    if (t_root.x >= t_root.y || t_root.y <= 0) {
+        discard;
+    }
+
+    float t = max(t_root.x, 0);
+    uint voxel = EMPTY;
+    vec3 hit_lo = vec3(0);
+    float hit_extent = 0;
+    for (int i_step = 0; i_step < MAX_STEPS && t < t_root.y; ++i_step) {
+        vec3 p = origin + t * dir;
+        uint i_node = octree[1];
+        vec3 lo = vec3(0);
+        float node_extent = extent;
+        // Bounded by the depth of the tree, in case the nodes are broken
+        for (uint depth = 0; depth < octree[0] && node_child(i_node, 0) != EMPTY; ++depth) {
+            node_extent *= 0.5;
+            vec3 mid = lo + node_extent;
+            uvec3 tie = uvec3(equal(p, mid)) & uvec3(greaterThan(dir, vec3(0)));
+            uvec3 upper = uvec3(greaterThan(p, mid)) | tie;
+            lo += node_extent * vec3(upper);
+            i_node = node_child(i_node, upper.x | (upper.y << 1) | (upper.z << 2));
+        }
+        if (node_voxel(i_node) != EMPTY) {
+            voxel = node_voxel(i_node);
+            hit_lo = lo;
+            hit_extent = node_extent;
+            break;
+        }
+        vec2 t_node = intersect_box(origin, inv_dir, lo, lo + node_extent);
+        t = max(t_node.y, t) + STEP_EPSILON;
+    }
+    if (voxel == EMPTY) {
+        discard;
+    }
+
+    vec3 t0 = (hit_lo - origin) * inv_dir;
+    vec3 t1 = (hit_lo + hit_extent - origin) * inv_dir;
+    vec3 t_near = min(t0, t1);
+    float t_enter = max(max(t_near.x, t_near.y), t_near.z);
+    vec3 norm = -sign(dir) * vec3(equal(t_near, vec3(t_enter)));
+    if (t_enter < 0) {
+        norm = -dir;
+    }
+    t = max(t_enter, 0);
+
+    vec3 hit_pos = params.transform.xyz + params.transform.w * (origin + t * dir);
+    vec4 hit_clip = cam.mat_view_proj * vec4(hit_pos, 1);
+    gl_FragDepth = hit_clip.z / hit_clip.w;
+
+    vec3 albedo = 0.2 + 0.8 * fract(float(voxel) * vec3(0.618034, 0.414214, 0.732051) + 0.25);
+    float emissive = 0;
+    if (voxel < material_count) {
+        albedo = materials[voxel].color.rgb;
+        emissive = materials[voxel].params.x;
+    }
+    float diffuse = max(dot(norm, -LIGHT_DIR), 0);
+    out_color = vec4(albedo * (0.2 + 0.8 * diffuse + emissive), 1);
+}
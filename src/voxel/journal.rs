@@ -0,0 +1,202 @@
+use super::octree::{BrushMode, Octree, Shape};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter},
+    mem,
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Edit {
+    Set {
+        offset: [usize; 3],
+        extent: [usize; 3],
+        voxel: usize,
+    },
+    Brush {
+        shape: Shape,
+        mode: BrushMode,
+        voxel: usize,
+    },
+}
+
+// Previous contents of the box `offset + [0, extent)` touched by an edit
+#[derive(Debug, Clone)]
+struct Entry {
+    edit: Edit,
+    offset: [usize; 3],
+    extent: [usize; 3],
+    boxes: Vec<([usize; 3], [usize; 3], usize)>,
+}
+
+// Undo and redo history of the edits of a tree. Previous contents are kept
+// for the latest edits within `memory_limit` bytes, while the edits themselves
+// are all kept so the session can be saved and replayed.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    edits: Vec<Edit>,
+    undo: VecDeque<Entry>,
+    redo: Vec<Edit>,
+    memory: usize,
+    memory_limit: usize,
+}
+
+impl Edit {
+    pub fn apply(&self, tree: &mut Octree) {
+        match *self {
+            Self::Set {
+                offset,
+                extent,
+                voxel,
+            } => tree.set(offset, extent, voxel),
+            Self::Brush { shape, mode, voxel } => tree.apply_brush(&shape, mode, voxel),
+        }
+    }
+
+    // Box containing every voxel the edit can change
+    fn region(&self, tree: &Octree) -> ([usize; 3], [usize; 3]) {
+        let (shape, mode) = match *self {
+            Self::Set { offset, extent, .. } => return (offset, extent),
+            Self::Brush { shape, mode, .. } => (shape, mode),
+        };
+        match (mode, shape.bounds()) {
+            (BrushMode::Intersect, _) | (_, None) => ([0; 3], [tree.extent(); 3]),
+            (_, Some((lo, hi))) => {
+                let lo = lo.map(|x| (x - 0.5).ceil().max(0.0) as usize);
+                let hi = hi.map(|x| ((x - 0.5).floor() + 1.0).max(0.0) as usize);
+                (lo, [0, 1, 2].map(|i| hi[i].saturating_sub(lo[i])))
+            }
+        }
+    }
+}
+
+impl Entry {
+    fn capture(tree: &Octree, edit: Edit) -> Self {
+        let (offset, extent) = edit.region(tree);
+        let end = [0, 1, 2].map(|i| offset[i] + extent[i]);
+        let mut boxes = Vec::new();
+        if !extent.contains(&0) {
+            for (leaf_offset, leaf_extent, voxel) in tree.leaves().within(offset, extent) {
+                if voxel == !0 {
+                    continue;
+                }
+                let lo = [0, 1, 2].map(|i| leaf_offset[i].max(offset[i]));
+                let hi = [0, 1, 2].map(|i| (leaf_offset[i] + leaf_extent).min(end[i]));
+                boxes.push((lo, [0, 1, 2].map(|i| hi[i] - lo[i]), voxel));
+            }
+        }
+        Self {
+            edit,
+            offset,
+            extent,
+            boxes,
+        }
+    }
+
+    fn restore(&self, tree: &mut Octree) {
+        tree.set(self.offset, self.extent, !0);
+        for &(offset, extent, voxel) in &self.boxes {
+            tree.set(offset, extent, voxel);
+        }
+    }
+
+    fn memory(&self) -> usize {
+        mem::size_of::<Self>() + mem::size_of_val(&self.boxes[..])
+    }
+}
+
+impl Journal {
+    #[allow(unused)]
+    pub fn new(memory_limit: usize) -> Self {
+        Self {
+            edits: Vec::new(),
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            memory: 0,
+            memory_limit,
+        }
+    }
+
+    // Applied edits in order, including the ones which can no longer be undone
+    #[allow(unused)]
+    pub fn edits(&self) -> &[Edit] {
+        &self.edits
+    }
+
+    #[allow(unused)]
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    #[allow(unused)]
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn apply(&mut self, tree: &mut Octree, edit: Edit) {
+        self.redo.clear();
+        self.apply_recorded(tree, edit);
+    }
+
+    #[allow(unused)]
+    pub fn undo(&mut self, tree: &mut Octree) -> bool {
+        let Some(entry) = self.undo.pop_back() else {
+            return false;
+        };
+        self.memory -= entry.memory();
+        entry.restore(tree);
+        self.edits.pop();
+        self.redo.push(entry.edit);
+        true
+    }
+
+    #[allow(unused)]
+    pub fn redo(&mut self, tree: &mut Octree) -> bool {
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        self.apply_recorded(tree, edit);
+        true
+    }
+
+    #[allow(unused)]
+    pub fn try_save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let file = File::create(path)?;
+        let writer = BufWriter::new(file);
+        Ok(serde_json::to_writer_pretty(writer, &self.edits)?)
+    }
+
+    // Applies the saved edits on top of `tree`, e.g. a freshly loaded base scene
+    #[allow(unused)]
+    pub fn try_replay(
+        path: impl AsRef<Path>,
+        tree: &mut Octree,
+        memory_limit: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        let edits: Vec<Edit> = serde_json::from_reader(reader)?;
+        let mut self_ = Self::new(memory_limit);
+        for edit in edits {
+            self_.apply(tree, edit);
+        }
+        Ok(self_)
+    }
+
+    fn apply_recorded(&mut self, tree: &mut Octree, edit: Edit) {
+        let entry = Entry::capture(tree, edit);
+        edit.apply(tree);
+        self.memory += entry.memory();
+        self.undo.push_back(entry);
+        self.edits.push(edit);
+        while self.memory > self.memory_limit {
+            let Some(entry) = self.undo.pop_front() else {
+                break;
+            };
+            self.memory -= entry.memory();
+        }
+    }
+}
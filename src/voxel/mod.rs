@@ -1,8 +1,31 @@
+pub mod journal;
 pub mod octree;
+pub mod palette;
+pub mod vox;
+pub mod voxelize;
+pub mod world;
 
-#[derive(Debug, Default, Clone, Copy)]
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 #[allow(unused)]
 pub struct VoxelInfo {
+    name: String,
     diffuse_color: [f32; 3],
     opacity: f32,
+    emissive: f32,
+    roughness: f32,
+}
+
+impl Default for VoxelInfo {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            diffuse_color: [1.0; 3],
+            opacity: 1.0,
+            emissive: 0.0,
+            roughness: 1.0,
+        }
+    }
 }
@@ -1,24 +1,83 @@
-use std::collections::{HashMap, VecDeque};
+mod brush;
+mod connectivity;
+mod dag;
+mod file;
+mod generate;
+mod iter;
+mod lod;
+mod merge;
+mod mesh;
+mod morton;
+mod physics;
+mod raycast;
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    mem,
+    ops::Range,
+};
 
 use crate::{
     math::{vec3, Vector},
     Vertex,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(unused)]
+pub use brush::{BrushMode, Shape};
+#[allow(unused)]
+pub use connectivity::Component;
+#[allow(unused)]
+pub use dag::OctreeDag;
+#[allow(unused)]
+pub use file::OctreeFileError;
+#[allow(unused)]
+pub use generate::{sphere, Caves, Generator, Heightmap, Sdf, ValueNoise};
+#[allow(unused)]
+pub use iter::{Leaves, Visitor};
+#[allow(unused)]
+pub use lod::LodPolicy;
+#[allow(unused)]
+pub use merge::MergeOp;
+#[allow(unused)]
+pub use morton::{morton_3, unmorton_3};
+#[allow(unused)]
+pub use physics::SweepHit;
+#[allow(unused)]
+pub use raycast::{RayHit, RayLeaf, RayLeaves};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Node {
     voxel: usize,
     children: [usize; 8],
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct Octree {
     nodes: Vec<Node>,
     free_nodes: Vec<usize>,
+    // Bit set of nodes changed since the last `take_dirty_ranges`
+    dirty: Vec<u64>,
+    // Hashes of subtree contents, 0 where unknown. Edits mark every node on
+    // their path from the root dirty, which resets the hashes of all of them.
+    subtree_hashes: RefCell<Vec<u64>>,
     root: usize,
     log_extent: usize,
 }
 
+// Dirty flags only concern uploads and hashes are a cache, equal trees may
+// differ in them
+impl PartialEq for Octree {
+    fn eq(&self, other: &Self) -> bool {
+        self.nodes == other.nodes
+            && self.free_nodes == other.free_nodes
+            && self.root == other.root
+            && self.log_extent == other.log_extent
+    }
+}
+
+impl Eq for Octree {}
+
 impl Default for Node {
     fn default() -> Self {
         Self::leaf(!0)
@@ -49,11 +108,21 @@ impl Octree {
         Self {
             nodes: vec![Node::default()],
             free_nodes: Vec::new(),
+            dirty: Vec::new(),
+            subtree_hashes: RefCell::default(),
             root: 0,
             log_extent: 0,
         }
     }
 
+    #[allow(unused)]
+    pub fn with_log_extent(log_extent: usize) -> Self {
+        Self {
+            log_extent,
+            ..Self::new()
+        }
+    }
+
     #[allow(unused)]
     pub fn capacity(&self) -> usize {
         self.nodes.len()
@@ -69,6 +138,13 @@ impl Octree {
         self.log_extent
     }
 
+    // Trees are kept canonical, so an empty one is a single empty leaf
+    #[allow(unused)]
+    pub fn is_empty(&self) -> bool {
+        self.nodes[self.root] == Node::leaf(!0)
+    }
+
+    #[allow(unused)]
     pub fn from_voxels(voxels: &[usize]) -> Self {
         let mut log_extent = 0;
         while 1 << (3 * log_extent) < voxels.len() {
@@ -77,37 +153,14 @@ impl Octree {
         if 1 << (3 * log_extent) > voxels.len() {
             panic!("Array size is not a precise 8^n");
         }
-        let mut n_nodes = 0;
-        for i in 0..=log_extent {
-            n_nodes += 1 << (3 * i);
-        }
-        let mut self_ = Self {
-            nodes: vec![Node::default(); n_nodes],
-            free_nodes: Vec::new(),
-            root: 0,
-            log_extent,
-        };
-
-        let leaf_off = n_nodes - (1 << (3 * log_extent));
+        let mut morton = vec![!0; voxels.len()];
         for (i_voxel, &voxel) in voxels.iter().enumerate() {
             let x = ((1 << log_extent) - 1) & i_voxel;
             let y = ((1 << log_extent) - 1) & (i_voxel >> log_extent);
             let z = ((1 << log_extent) - 1) & (i_voxel >> (2 * log_extent));
-            let mut i_leaf = 0;
-            for i in 0..log_extent {
-                i_leaf |=
-                    (((x >> i) & 1) | (((y >> i) & 1) << 1) | (((z >> i) & 1) << 2)) << (3 * i);
-            }
-            let i_node = i_leaf + leaf_off;
-            self_.nodes[i_node].voxel = voxel;
+            morton[morton_3([x, y, z])] = voxel;
         }
-        for i_node in (0..leaf_off).rev() {
-            for i_child in 0..8 {
-                self_.nodes[i_node].children[i_child] = 8 * i_node + 1 + i_child;
-            }
-            self_.merge_branch(i_node);
-        }
-        self_.shrinked()
+        Self::from_morton(&morton)
     }
 
     #[allow(unused)]
@@ -137,7 +190,11 @@ impl Octree {
 
     #[allow(unused)]
     pub fn set(&mut self, [x, y, z]: [usize; 3], [ex, ey, ez]: [usize; 3], voxel: usize) {
-        let needed_extent = (x + ex).max(y + ey).max(z + ez);
+        self.grow((x + ex).max(y + ey).max(z + ez));
+        self.set_descend([x, y, z], [ex, ey, ez], voxel, self.root, self.log_extent);
+    }
+
+    fn grow(&mut self, needed_extent: usize) {
         while needed_extent > 1 << self.log_extent {
             let new_root = self.new_leaf(!0);
             self.nodes[new_root].children[0] = self.root;
@@ -145,10 +202,10 @@ impl Octree {
                 let new_leaf = self.new_leaf(!0);
                 self.nodes[new_root].children[i] = new_leaf;
             }
+            self.mark_dirty(new_root);
             self.root = new_root;
             self.log_extent += 1;
         }
-        self.set_descend([x, y, z], [ex, ey, ez], voxel, self.root, self.log_extent);
     }
 
     #[allow(unused)]
@@ -168,6 +225,7 @@ impl Octree {
         if offset == [0; 3] && extent == [1 << node_log_extent; 3] {
             self.drop_children(i_node);
             self.nodes[i_node].voxel = voxel;
+            self.mark_dirty(i_node);
             return;
         }
         assert_ne!(node_log_extent, 0);
@@ -179,13 +237,14 @@ impl Octree {
             let mut next_offset = [0; 3];
             let mut next_extent = [0; 3];
             for i in 0..3 {
+                let end = offset[i] + extent[i];
                 if i_child & (1 << i) != 0 {
                     next_offset[i] = half_extent.max(offset[i]) - half_extent;
+                    next_extent[i] = half_extent.max(end) - half_extent - next_offset[i];
                 } else {
                     next_offset[i] = offset[i];
+                    next_extent[i] = end.min(half_extent).max(offset[i]) - offset[i];
                 }
-                next_extent[i] =
-                    (offset[i] + extent[i]).min(half_extent).max(next_offset[i]) - next_offset[i];
             }
             self.set_descend(
                 next_offset,
@@ -230,14 +289,20 @@ impl Octree {
         Self {
             nodes,
             free_nodes: Vec::new(),
+            dirty: Vec::new(),
+            subtree_hashes: RefCell::default(),
             root: 0,
             log_extent: self.log_extent,
         }
     }
 
+    // Moves every node, so all of them are marked dirty
     #[allow(unused)]
     pub fn shrink(&mut self) {
         *self = self.shrinked();
+        for i_node in 0..self.nodes.len() {
+            self.mark_dirty(i_node);
+        }
     }
 
     #[allow(unused)]
@@ -245,17 +310,62 @@ impl Octree {
         assert!(self.free_nodes.is_empty());
         assert!(self.root == 0);
         let mut out = vec![0; 4 + 12 * self.nodes.len()];
-        out[0] = self.log_extent as _;
+        out[..4].copy_from_slice(&self.gpu_header());
         for i_node in 0..self.nodes.len() {
-            let node = self.nodes[i_node];
-            out[4 + 12 * i_node] = node.voxel as _;
-            for (i_child, &j_node) in node.children.iter().enumerate() {
-                out[8 + 12 * i_node + i_child] = j_node as _;
+            out[4 + 12 * i_node..][..12].copy_from_slice(&self.gpu_node(i_node));
+        }
+        out
+    }
+
+    // [0] --- log extent, [1] --- root node
+    pub fn gpu_header(&self) -> [u32; 4] {
+        [self.log_extent as _, self.root as _, 0, 0]
+    }
+
+    // [0] --- voxel, [4..12] --- children, free nodes are empty leaves
+    pub fn gpu_node(&self, i_node: usize) -> [u32; 12] {
+        let node = self.nodes[i_node];
+        let mut out = [0; 12];
+        out[0] = node.voxel as _;
+        for (i_child, &j_node) in node.children.iter().enumerate() {
+            out[4 + i_child] = j_node as _;
+        }
+        out
+    }
+
+    // Ranges of nodes changed since the previous call, clearing the flags.
+    // Ranges closer than `max_gap` are joined to save on copy regions.
+    #[allow(unused)]
+    pub fn take_dirty_ranges(&mut self, max_gap: usize) -> Vec<Range<usize>> {
+        let mut out: Vec<Range<usize>> = Vec::new();
+        for (i_word, word) in self.dirty.iter_mut().enumerate() {
+            let mut bits = mem::take(word);
+            while bits != 0 {
+                let shift = bits.trailing_zeros() as usize;
+                let len = (bits >> shift).trailing_ones() as usize;
+                bits &= !(u64::MAX >> (64 - len) << shift);
+                let start = 64 * i_word + shift;
+                match out.last_mut() {
+                    Some(last) if start <= last.end + max_gap => last.end = start + len,
+                    _ => out.push(start..start + len),
+                }
             }
         }
+        self.dirty.clear();
         out
     }
 
+    fn mark_dirty(&mut self, i_node: usize) {
+        let i_word = i_node / 64;
+        if i_word >= self.dirty.len() {
+            self.dirty.resize(i_word + 1, 0);
+        }
+        self.dirty[i_word] |= 1 << (i_node % 64);
+        if let Some(hash) = self.subtree_hashes.get_mut().get_mut(i_node) {
+            *hash = 0;
+        }
+    }
+
     #[allow(unused)]
     fn new_leaf(&mut self, voxel: usize) -> usize {
         let i = match self.free_nodes.pop() {
@@ -266,6 +376,7 @@ impl Octree {
             }
         };
         self.nodes[i] = Node::leaf(voxel);
+        self.mark_dirty(i);
         i
     }
 
@@ -283,6 +394,7 @@ impl Octree {
             self.drop_node(j_node);
         }
         self.nodes[i_node].children = [!0; 8];
+        self.mark_dirty(i_node);
     }
 
     #[allow(unused)]
@@ -293,6 +405,7 @@ impl Octree {
             let i_new_leaf = self.new_leaf(voxel);
             self.nodes[i_node].children[i] = i_new_leaf;
         }
+        self.mark_dirty(i_node);
     }
 
     fn merge_branch(&mut self, i_node: usize) {
@@ -300,6 +413,7 @@ impl Octree {
         assert!(v.is_branch());
         let voxel = self.nodes[v.children[0]].voxel;
         self.nodes[i_node].voxel = voxel;
+        self.mark_dirty(i_node);
         for j_node in v.children {
             let u = &self.nodes[j_node];
             if u.is_branch() || u.voxel != voxel {
@@ -310,40 +424,10 @@ impl Octree {
     }
 
     pub fn debug_boxes(&self) -> Vec<([usize; 3], usize)> {
-        let mut acc = Vec::new();
-        self.debug_boxes_descend(&mut acc, self.root, [0; 3], self.log_extent);
-        acc
-    }
-
-    fn debug_boxes_descend(
-        &self,
-        acc: &mut Vec<([usize; 3], usize)>,
-        i_node: usize,
-        offset: [usize; 3],
-        node_log_extent: usize,
-    ) {
-        let node = &self.nodes[i_node];
-        if node.is_leaf() {
-            if node.voxel != !0 {
-                acc.push((offset, 1 << node_log_extent));
-            }
-        } else {
-            let half_extent = 1 << (node_log_extent - 1);
-            for i_child in 0..8 {
-                let mut next_offset = offset;
-                for (i, no) in next_offset.iter_mut().enumerate() {
-                    if i_child & (1 << i) != 0 {
-                        *no += half_extent;
-                    }
-                }
-                self.debug_boxes_descend(
-                    acc,
-                    node.children[i_child],
-                    next_offset,
-                    node_log_extent - 1,
-                );
-            }
-        }
+        self.leaves()
+            .filter(|&(_, _, voxel)| voxel != !0)
+            .map(|(offset, extent, _)| (offset, extent))
+            .collect()
     }
 
     #[allow(unused)]
@@ -352,7 +436,10 @@ impl Octree {
         let mut vertices = Vec::new();
         let mut index_of = HashMap::new();
 
-        for ([x, y, z], e) in self.debug_boxes() {
+        for ([x, y, z], e, voxel) in self.leaves() {
+            if voxel == !0 {
+                continue;
+            }
             //    6--------7
             //   /|       /|
             //  / |      / |
@@ -364,10 +451,10 @@ impl Octree {
             // 0--------1
 
             // -Z
-            let i = vertex_index(&mut index_of, &mut vertices, [x, y, z, 0]);
-            let ii = vertex_index(&mut index_of, &mut vertices, [x + e, y, z, 0]);
-            let iii = vertex_index(&mut index_of, &mut vertices, [x, y + e, z, 0]);
-            let iv = vertex_index(&mut index_of, &mut vertices, [x + e, y + e, z, 0]);
+            let i = vertex_index(&mut index_of, &mut vertices, [x, y, z, 0, voxel]);
+            let ii = vertex_index(&mut index_of, &mut vertices, [x + e, y, z, 0, voxel]);
+            let iii = vertex_index(&mut index_of, &mut vertices, [x, y + e, z, 0, voxel]);
+            let iv = vertex_index(&mut index_of, &mut vertices, [x + e, y + e, z, 0, voxel]);
             indices.push(i as _);
             indices.push(iii as _);
             indices.push(iv as _);
@@ -376,10 +463,14 @@ impl Octree {
             indices.push(i as _);
 
             // +Z
-            let i = vertex_index(&mut index_of, &mut vertices, [x, y, z + e, 1]);
-            let ii = vertex_index(&mut index_of, &mut vertices, [x + e, y, z + e, 1]);
-            let iii = vertex_index(&mut index_of, &mut vertices, [x, y + e, z + e, 1]);
-            let iv = vertex_index(&mut index_of, &mut vertices, [x + e, y + e, z + e, 1]);
+            let i = vertex_index(&mut index_of, &mut vertices, [x, y, z + e, 1, voxel]);
+            let ii = vertex_index(&mut index_of, &mut vertices, [x + e, y, z + e, 1, voxel]);
+            let iii = vertex_index(&mut index_of, &mut vertices, [x, y + e, z + e, 1, voxel]);
+            let iv = vertex_index(
+                &mut index_of,
+                &mut vertices,
+                [x + e, y + e, z + e, 1, voxel],
+            );
             indices.push(i as _);
             indices.push(ii as _);
             indices.push(iv as _);
@@ -388,10 +479,10 @@ impl Octree {
             indices.push(i as _);
 
             // -Y
-            let i = vertex_index(&mut index_of, &mut vertices, [x, y, z, 2]);
-            let ii = vertex_index(&mut index_of, &mut vertices, [x + e, y, z, 2]);
-            let iii = vertex_index(&mut index_of, &mut vertices, [x, y, z + e, 2]);
-            let iv = vertex_index(&mut index_of, &mut vertices, [x + e, y, z + e, 2]);
+            let i = vertex_index(&mut index_of, &mut vertices, [x, y, z, 2, voxel]);
+            let ii = vertex_index(&mut index_of, &mut vertices, [x + e, y, z, 2, voxel]);
+            let iii = vertex_index(&mut index_of, &mut vertices, [x, y, z + e, 2, voxel]);
+            let iv = vertex_index(&mut index_of, &mut vertices, [x + e, y, z + e, 2, voxel]);
             indices.push(i as _);
             indices.push(ii as _);
             indices.push(iv as _);
@@ -400,10 +491,14 @@ impl Octree {
             indices.push(i as _);
 
             // +Y
-            let i = vertex_index(&mut index_of, &mut vertices, [x, y + e, z, 3]);
-            let ii = vertex_index(&mut index_of, &mut vertices, [x + e, y + e, z, 3]);
-            let iii = vertex_index(&mut index_of, &mut vertices, [x, y + e, z + e, 3]);
-            let iv = vertex_index(&mut index_of, &mut vertices, [x + e, y + e, z + e, 3]);
+            let i = vertex_index(&mut index_of, &mut vertices, [x, y + e, z, 3, voxel]);
+            let ii = vertex_index(&mut index_of, &mut vertices, [x + e, y + e, z, 3, voxel]);
+            let iii = vertex_index(&mut index_of, &mut vertices, [x, y + e, z + e, 3, voxel]);
+            let iv = vertex_index(
+                &mut index_of,
+                &mut vertices,
+                [x + e, y + e, z + e, 3, voxel],
+            );
             indices.push(i as _);
             indices.push(iii as _);
             indices.push(iv as _);
@@ -412,10 +507,10 @@ impl Octree {
             indices.push(i as _);
 
             // -X
-            let i = vertex_index(&mut index_of, &mut vertices, [x, y, z, 4]);
-            let ii = vertex_index(&mut index_of, &mut vertices, [x, y + e, z, 4]);
-            let iii = vertex_index(&mut index_of, &mut vertices, [x, y, z + e, 4]);
-            let iv = vertex_index(&mut index_of, &mut vertices, [x, y + e, z + e, 4]);
+            let i = vertex_index(&mut index_of, &mut vertices, [x, y, z, 4, voxel]);
+            let ii = vertex_index(&mut index_of, &mut vertices, [x, y + e, z, 4, voxel]);
+            let iii = vertex_index(&mut index_of, &mut vertices, [x, y, z + e, 4, voxel]);
+            let iv = vertex_index(&mut index_of, &mut vertices, [x, y + e, z + e, 4, voxel]);
             indices.push(i as _);
             indices.push(iii as _);
             indices.push(iv as _);
@@ -424,10 +519,14 @@ impl Octree {
             indices.push(i as _);
 
             // +X
-            let i = vertex_index(&mut index_of, &mut vertices, [x + e, y, z, 5]);
-            let ii = vertex_index(&mut index_of, &mut vertices, [x + e, y + e, z, 5]);
-            let iii = vertex_index(&mut index_of, &mut vertices, [x + e, y, z + e, 5]);
-            let iv = vertex_index(&mut index_of, &mut vertices, [x + e, y + e, z + e, 5]);
+            let i = vertex_index(&mut index_of, &mut vertices, [x + e, y, z, 5, voxel]);
+            let ii = vertex_index(&mut index_of, &mut vertices, [x + e, y + e, z, 5, voxel]);
+            let iii = vertex_index(&mut index_of, &mut vertices, [x + e, y, z + e, 5, voxel]);
+            let iv = vertex_index(
+                &mut index_of,
+                &mut vertices,
+                [x + e, y + e, z + e, 5, voxel],
+            );
             indices.push(i as _);
             indices.push(ii as _);
             indices.push(iv as _);
@@ -440,10 +539,22 @@ impl Octree {
     }
 }
 
+// Offset of child `i_child` of a node at `offset`
+fn child_offset(offset: [usize; 3], node_log_extent: usize, i_child: usize) -> [usize; 3] {
+    let half_extent = 1 << (node_log_extent - 1);
+    let mut next_offset = offset;
+    for (i, no) in next_offset.iter_mut().enumerate() {
+        if i_child & (1 << i) != 0 {
+            *no += half_extent;
+        }
+    }
+    next_offset
+}
+
 fn vertex_index(
-    index_of: &mut HashMap<[usize; 4], usize>,
+    index_of: &mut HashMap<[usize; 5], usize>,
     vertices: &mut Vec<Vertex>,
-    key: [usize; 4],
+    key: [usize; 5],
 ) -> usize {
     use std::collections::hash_map::Entry;
     const NORMALS: [vec3; 6] = [
@@ -462,8 +573,40 @@ fn vertex_index(
             vertices.push(Vertex {
                 pos: Vector([key[0] as f32, key[1] as f32, key[2] as f32]),
                 norm: NORMALS[key[3]],
+                voxel: key[4] as _,
             });
             i
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Boxes ending in the upper half of a node used to be clipped against the
+    // lower half's bounds, spilling into voxels past their end
+    #[test]
+    fn set_upper_children() {
+        let mut tree = Octree::with_log_extent(3);
+        tree.set([0, 0, 0], [8, 1, 8], 1);
+        let lo = [5, 2, 3];
+        let hi = [7, 8, 7];
+        tree.set(lo, [0, 1, 2].map(|i| hi[i] - lo[i]), 2);
+        for z in 0..8 {
+            for y in 0..8 {
+                for x in 0..8 {
+                    let pos = [x, y, z];
+                    let expected = if (0..3).all(|i| (lo[i]..hi[i]).contains(&pos[i])) {
+                        2
+                    } else if y == 0 {
+                        1
+                    } else {
+                        !0
+                    };
+                    assert_eq!(tree.get(pos), expected, "{pos:?}");
+                }
+            }
+        }
+    }
+}
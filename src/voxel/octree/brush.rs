@@ -0,0 +1,292 @@
+use super::{child_offset, Octree};
+use crate::math::{vec3, Vector};
+use serde_derive::{Deserialize, Serialize};
+
+// Voxel `[x, y, z]` belongs to a shape when its centre `[x, y, z] + 0.5` does
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Shape {
+    Sphere {
+        center: vec3,
+        radius: f32,
+    },
+    Capsule {
+        a: vec3,
+        b: vec3,
+        radius: f32,
+    },
+    Cylinder {
+        a: vec3,
+        b: vec3,
+        radius: f32,
+    },
+    // Axes have to be orthonormal
+    OrientedBox {
+        center: vec3,
+        axes: [vec3; 3],
+        half_extents: vec3,
+    },
+    // Everything with `dot(normal, p) <= distance`
+    HalfSpace {
+        normal: vec3,
+        distance: f32,
+    },
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BrushMode {
+    // Fill the inside of the shape
+    Union,
+    // Empty the inside of the shape
+    Subtract,
+    // Empty the outside of the shape
+    Intersect,
+    // Change filled voxels inside of the shape, keeping empty ones
+    Replace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Coverage {
+    Inside,
+    Outside,
+    Partial,
+}
+
+impl Shape {
+    // Signed distance, negative inside. Never overestimates the distance
+    // to the surface, which is what the node classification relies on.
+    pub fn sdf(&self, p: vec3) -> f32 {
+        match *self {
+            Self::Sphere { center, radius } => (p - center).length() - radius,
+            Self::Capsule { a, b, radius } => {
+                let pa = p - a;
+                let ba = b - a;
+                let baba = ba.dot(ba);
+                let h = if baba > 0.0 {
+                    (pa.dot(ba) / baba).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (pa - ba * h).length() - radius
+            }
+            Self::Cylinder { a, b, radius } => {
+                let pa = p - a;
+                let ba = b - a;
+                let baba = ba.dot(ba);
+                if baba == 0.0 {
+                    return f32::INFINITY;
+                }
+                let paba = pa.dot(ba);
+                let x = (pa * baba - ba * paba).length() - radius * baba;
+                let y = (paba - 0.5 * baba).abs() - 0.5 * baba;
+                let x2 = x * x;
+                let y2 = y * y * baba;
+                let d = if x.max(y) < 0.0 {
+                    -x2.min(y2)
+                } else {
+                    x.max(0.0).powi(2) + if y > 0.0 { y2 } else { 0.0 }
+                };
+                d.signum() * d.abs().sqrt() / baba
+            }
+            Self::OrientedBox {
+                center,
+                axes,
+                half_extents,
+            } => {
+                let pc = p - center;
+                let q = Vector([0, 1, 2].map(|i| pc.dot(axes[i]).abs() - half_extents.0[i]));
+                let outside = Vector(q.0.map(|qi| qi.max(0.0))).length();
+                let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+                outside + inside
+            }
+            Self::HalfSpace { normal, distance } => {
+                let length = normal.length();
+                (normal.dot(p) - distance) / length
+            }
+        }
+    }
+
+    #[allow(unused)]
+    pub fn translated(&self, t: vec3) -> Self {
+        match *self {
+            Self::Sphere { center, radius } => Self::Sphere {
+                center: center + t,
+                radius,
+            },
+            Self::Capsule { a, b, radius } => Self::Capsule {
+                a: a + t,
+                b: b + t,
+                radius,
+            },
+            Self::Cylinder { a, b, radius } => Self::Cylinder {
+                a: a + t,
+                b: b + t,
+                radius,
+            },
+            Self::OrientedBox {
+                center,
+                axes,
+                half_extents,
+            } => Self::OrientedBox {
+                center: center + t,
+                axes,
+                half_extents,
+            },
+            Self::HalfSpace { normal, distance } => Self::HalfSpace {
+                normal,
+                distance: distance + normal.dot(t),
+            },
+        }
+    }
+
+    // Axis-aligned bounding box as `(min, max)`, `None` for unbounded shapes
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        let (lo, hi) = match *self {
+            Self::Sphere { center, radius } => (center - radius, center + radius),
+            Self::Capsule { a, b, radius } => {
+                let lo = Vector([0, 1, 2].map(|i| a.0[i].min(b.0[i])));
+                let hi = Vector([0, 1, 2].map(|i| a.0[i].max(b.0[i])));
+                (lo - radius, hi + radius)
+            }
+            Self::Cylinder { a, b, radius } => {
+                let ba = b - a;
+                let baba = ba.dot(ba);
+                if baba == 0.0 {
+                    return Some(([0.0; 3], [0.0; 3]));
+                }
+                let e = Vector(ba.0.map(|bi| radius * (1.0 - bi * bi / baba).max(0.0).sqrt()));
+                let lo = Vector([0, 1, 2].map(|i| a.0[i].min(b.0[i])));
+                let hi = Vector([0, 1, 2].map(|i| a.0[i].max(b.0[i])));
+                (lo - e, hi + e)
+            }
+            Self::OrientedBox {
+                center,
+                axes,
+                half_extents,
+            } => {
+                let mut e = vec3::default();
+                for (axis, h) in axes.iter().zip(half_extents.0) {
+                    e += Vector(axis.0.map(|ai| ai.abs() * h));
+                }
+                (center - e, center + e)
+            }
+            Self::HalfSpace { .. } => return None,
+        };
+        Some((lo.0, hi.0))
+    }
+
+    // Whether the voxels of the box `offset + [0, extent)^3` are all inside,
+    // all outside or some of each
+    fn coverage(
+        &self,
+        bounds: Option<([f32; 3], [f32; 3])>,
+        offset: [usize; 3],
+        extent: usize,
+    ) -> Coverage {
+        let first = Vector(offset.map(|o| o as f32 + 0.5));
+        let last = first + (extent - 1) as f32;
+        if let Some((lo, hi)) = bounds {
+            for i in 0..3 {
+                if last.0[i] < lo[i] || first.0[i] > hi[i] {
+                    return Coverage::Outside;
+                }
+            }
+        }
+        let center = (first + last) * 0.5;
+        // All voxel centres are within this distance from the box centre
+        let radius = 0.5 * 3f32.sqrt() * (extent - 1) as f32;
+        let d = self.sdf(center);
+        if d + radius <= 0.0 {
+            Coverage::Inside
+        } else if d - radius > 0.0 || extent == 1 {
+            Coverage::Outside
+        } else {
+            Coverage::Partial
+        }
+    }
+}
+
+impl Octree {
+    #[allow(unused)]
+    pub fn apply_brush(&mut self, shape: &Shape, mode: BrushMode, voxel: usize) {
+        let bounds = shape.bounds();
+        if let (BrushMode::Union, Some((_, hi))) = (mode, bounds) {
+            let needed_extent = hi.iter().fold(0.0f32, |acc, &h| acc.max(h.ceil()));
+            self.grow(needed_extent as usize);
+        }
+        self.apply_brush_clipped(shape, mode, voxel);
+    }
+
+    // Same as `apply_brush`, but never grows the tree, so voxels past `extent` stay untouched
+    #[allow(unused)]
+    pub fn apply_brush_clipped(&mut self, shape: &Shape, mode: BrushMode, voxel: usize) {
+        let bounds = shape.bounds();
+        self.brush_descend(
+            shape,
+            bounds,
+            mode,
+            voxel,
+            self.root,
+            [0; 3],
+            self.log_extent,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn brush_descend(
+        &mut self,
+        shape: &Shape,
+        bounds: Option<([f32; 3], [f32; 3])>,
+        mode: BrushMode,
+        voxel: usize,
+        i_node: usize,
+        offset: [usize; 3],
+        node_log_extent: usize,
+    ) {
+        let node = self.nodes[i_node];
+        let coverage = shape.coverage(bounds, offset, 1 << node_log_extent);
+        let fill = match (mode, coverage) {
+            (BrushMode::Union, Coverage::Inside) => Some(voxel),
+            (BrushMode::Subtract, Coverage::Inside) => Some(!0),
+            (BrushMode::Intersect, Coverage::Outside) => Some(!0),
+            (BrushMode::Replace, Coverage::Inside) if node.is_leaf() => {
+                Some(if node.voxel == !0 { !0 } else { voxel })
+            }
+            (_, Coverage::Partial) | (BrushMode::Replace, Coverage::Inside) => None,
+            _ => return,
+        };
+        if let Some(fill) = fill {
+            self.drop_children(i_node);
+            self.nodes[i_node].voxel = fill;
+            return;
+        }
+
+        if node.is_leaf() {
+            // Only split when some of the voxels would actually change
+            let target = match mode {
+                BrushMode::Union => voxel,
+                BrushMode::Subtract | BrushMode::Intersect => !0,
+                BrushMode::Replace if node.voxel == !0 => return,
+                BrushMode::Replace => voxel,
+            };
+            if node.voxel == target {
+                return;
+            }
+            self.split_leaf(i_node);
+        }
+        for i_child in 0..8 {
+            let next_offset = child_offset(offset, node_log_extent, i_child);
+            self.brush_descend(
+                shape,
+                bounds,
+                mode,
+                voxel,
+                self.nodes[i_node].children[i_child],
+                next_offset,
+                node_log_extent - 1,
+            );
+        }
+        self.merge_branch(i_node);
+    }
+}
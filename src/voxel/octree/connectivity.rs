@@ -0,0 +1,206 @@
+use super::{child_offset, Octree};
+use std::collections::VecDeque;
+
+// Set of face-connected leaves, bounds are `offset + [0, extent)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Component {
+    pub offset: [usize; 3],
+    pub extent: [usize; 3],
+    pub voxel_count: usize,
+}
+
+impl Octree {
+    // Leaf containing the voxel as `(offset, extent, voxel)`
+    #[allow(unused)]
+    pub fn leaf_at(&self, p: [usize; 3]) -> Option<([usize; 3], usize, usize)> {
+        let (i_node, offset, node_log_extent) = self.find_leaf(p)?;
+        Some((offset, 1 << node_log_extent, self.nodes[i_node].voxel))
+    }
+
+    // Leaves touching the face of the cube `offset + [0, extent)^3` looking
+    // along `axis` in positive or negative direction, whatever their size
+    #[allow(unused)]
+    pub fn face_neighbors(
+        &self,
+        offset: [usize; 3],
+        extent: usize,
+        axis: usize,
+        positive: bool,
+    ) -> Vec<([usize; 3], usize, usize)> {
+        let mut out = Vec::new();
+        self.for_each_face_neighbor(offset, extent, axis, positive, |i_node, offset, e| {
+            out.push((offset, e, self.nodes[i_node].voxel));
+        });
+        out
+    }
+
+    // Leaves reachable from the one containing `start` through faces of
+    // leaves with `passable` voxels, `start` itself has to be passable
+    #[allow(unused)]
+    pub fn flood_fill(
+        &self,
+        start: [usize; 3],
+        passable: impl Fn(usize) -> bool,
+    ) -> Vec<([usize; 3], usize, usize)> {
+        let mut out = Vec::new();
+        let Some((i_node, offset, node_log_extent)) = self.find_leaf(start) else {
+            return out;
+        };
+        if !passable(self.nodes[i_node].voxel) {
+            return out;
+        }
+        let mut visited = vec![false; self.nodes.len()];
+        self.flood_leaves(
+            &mut visited,
+            (i_node, offset, 1 << node_log_extent),
+            |a, b| passable(a) && passable(b),
+            |offset, extent, voxel| out.push((offset, extent, voxel)),
+        );
+        out
+    }
+
+    // Groups non-empty leaves into components, two touching leaves are in
+    // the same one if `connected` holds for their voxels
+    #[allow(unused)]
+    pub fn connected_components(&self, connected: impl Fn(usize, usize) -> bool) -> Vec<Component> {
+        let mut out = Vec::new();
+        let mut visited = vec![false; self.nodes.len()];
+        let mut seeds = Vec::new();
+        self.for_each_leaf_in(
+            self.root,
+            [0; 3],
+            self.log_extent,
+            [0; 3],
+            [1 << self.log_extent; 3],
+            &mut |i_node, offset, extent| seeds.push((i_node, offset, extent)),
+        );
+        for seed in seeds {
+            if visited[seed.0] || self.nodes[seed.0].voxel == !0 {
+                continue;
+            }
+            let mut component = Component {
+                offset: seed.1,
+                extent: [0; 3],
+                voxel_count: 0,
+            };
+            let mut end = seed.1;
+            self.flood_leaves(
+                &mut visited,
+                seed,
+                |a, b| a != !0 && b != !0 && connected(a, b),
+                |offset, extent, _| {
+                    for i in 0..3 {
+                        component.offset[i] = component.offset[i].min(offset[i]);
+                        end[i] = end[i].max(offset[i] + extent);
+                    }
+                    component.voxel_count += extent * extent * extent;
+                },
+            );
+            component.extent = [0, 1, 2].map(|i| end[i] - component.offset[i]);
+            out.push(component);
+        }
+        out
+    }
+
+    fn flood_leaves(
+        &self,
+        visited: &mut [bool],
+        start: (usize, [usize; 3], usize),
+        connected: impl Fn(usize, usize) -> bool,
+        mut f: impl FnMut([usize; 3], usize, usize),
+    ) {
+        let mut queue = VecDeque::new();
+        visited[start.0] = true;
+        queue.push_back(start);
+        while let Some((i_node, offset, extent)) = queue.pop_front() {
+            let voxel = self.nodes[i_node].voxel;
+            f(offset, extent, voxel);
+            for axis in 0..3 {
+                for positive in [false, true] {
+                    self.for_each_face_neighbor(offset, extent, axis, positive, |j_node, o, e| {
+                        if !visited[j_node] && connected(voxel, self.nodes[j_node].voxel) {
+                            visited[j_node] = true;
+                            queue.push_back((j_node, o, e));
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    fn for_each_face_neighbor(
+        &self,
+        offset: [usize; 3],
+        extent: usize,
+        axis: usize,
+        positive: bool,
+        mut f: impl FnMut(usize, [usize; 3], usize),
+    ) {
+        let mut lo = offset;
+        let mut hi = offset.map(|o| o + extent);
+        if positive {
+            lo[axis] = hi[axis];
+        } else if offset[axis] == 0 {
+            return;
+        } else {
+            lo[axis] -= 1;
+        }
+        hi[axis] = lo[axis] + 1;
+        self.for_each_leaf_in(self.root, [0; 3], self.log_extent, lo, hi, &mut f);
+    }
+
+    // Leaves intersecting the box `[lo, hi)`
+    fn for_each_leaf_in(
+        &self,
+        i_node: usize,
+        offset: [usize; 3],
+        node_log_extent: usize,
+        lo: [usize; 3],
+        hi: [usize; 3],
+        f: &mut impl FnMut(usize, [usize; 3], usize),
+    ) {
+        let extent = 1 << node_log_extent;
+        for i in 0..3 {
+            if hi[i] <= offset[i] || offset[i] + extent <= lo[i] {
+                return;
+            }
+        }
+        let node = &self.nodes[i_node];
+        if node.is_leaf() {
+            f(i_node, offset, extent);
+            return;
+        }
+        for i_child in 0..8 {
+            let next_offset = child_offset(offset, node_log_extent, i_child);
+            self.for_each_leaf_in(
+                node.children[i_child],
+                next_offset,
+                node_log_extent - 1,
+                lo,
+                hi,
+                f,
+            );
+        }
+    }
+
+    fn find_leaf(&self, [x, y, z]: [usize; 3]) -> Option<(usize, [usize; 3], usize)> {
+        if x.max(y).max(z) >= 1 << self.log_extent {
+            return None;
+        }
+        let mut i_node = self.root;
+        let mut offset = [0; 3];
+        let mut node_log_extent = self.log_extent;
+        while self.nodes[i_node].is_branch() {
+            node_log_extent -= 1;
+            let mut i_child = 0;
+            for (i, c) in [x, y, z].into_iter().enumerate() {
+                if (c >> node_log_extent) & 1 != 0 {
+                    i_child |= 1 << i;
+                    offset[i] += 1 << node_log_extent;
+                }
+            }
+            i_node = self.nodes[i_node].children[i_child];
+        }
+        Some((i_node, offset, node_log_extent))
+    }
+}
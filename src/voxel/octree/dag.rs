@@ -0,0 +1,111 @@
+use super::{Node, Octree, OctreeFileError};
+use std::{cell::RefCell, collections::HashMap, io::Write};
+
+// Identical subtrees share nodes, so the tree must not be edited in place and
+// only queries which do not care about sharing are exposed. Walks marking
+// visited nodes by index, like `flood_fill`, would be wrong on it as well.
+//
+// Meant for static scenes and export only: the renderer streams edits into
+// the node pool by node index, which needs the plain `Octree`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OctreeDag {
+    tree: Octree,
+    tree_node_count: usize,
+}
+
+impl Octree {
+    #[allow(unused)]
+    pub fn to_dag(&self) -> OctreeDag {
+        let mut nodes = Vec::new();
+        let mut index_of = HashMap::new();
+        let mut tree_node_count = 0;
+        let root = self.dedup_descend(&mut nodes, &mut index_of, &mut tree_node_count, self.root);
+        assert_eq!(root, nodes.len() - 1);
+
+        // Children always precede parents, reversing puts the root first
+        let n_nodes = nodes.len();
+        nodes.reverse();
+        for node in &mut nodes {
+            for j_node in &mut node.children {
+                if *j_node != !0 {
+                    *j_node = n_nodes - 1 - *j_node;
+                }
+            }
+        }
+        OctreeDag {
+            tree: Self {
+                nodes,
+                free_nodes: Vec::new(),
+                dirty: Vec::new(),
+                subtree_hashes: RefCell::default(),
+                root: 0,
+                log_extent: self.log_extent,
+            },
+            tree_node_count,
+        }
+    }
+
+    fn dedup_descend(
+        &self,
+        nodes: &mut Vec<Node>,
+        index_of: &mut HashMap<Node, usize>,
+        tree_node_count: &mut usize,
+        i_node: usize,
+    ) -> usize {
+        *tree_node_count += 1;
+        let mut node = self.nodes[i_node];
+        if node.is_branch() {
+            for j_node in &mut node.children {
+                *j_node = self.dedup_descend(nodes, index_of, tree_node_count, *j_node);
+            }
+        }
+        *index_of.entry(node).or_insert_with(|| {
+            nodes.push(node);
+            nodes.len() - 1
+        })
+    }
+}
+
+impl OctreeDag {
+    #[allow(unused)]
+    pub fn tree_node_count(&self) -> usize {
+        self.tree_node_count
+    }
+
+    #[allow(unused)]
+    pub fn node_count(&self) -> usize {
+        self.tree.nodes.len()
+    }
+
+    #[allow(unused)]
+    pub fn extent(&self) -> usize {
+        self.tree.extent()
+    }
+
+    #[allow(unused)]
+    pub fn log_extent(&self) -> usize {
+        self.tree.log_extent()
+    }
+
+    #[allow(unused)]
+    pub fn get(&self, offset: [usize; 3]) -> usize {
+        self.tree.get(offset)
+    }
+
+    #[allow(unused)]
+    pub fn sample(&self, offset: [usize; 3], log_extent: usize) -> usize {
+        self.tree.sample(offset, log_extent)
+    }
+
+    // Same layout as `Octree::gpu_data()`, children may be shared
+    #[allow(unused)]
+    pub fn gpu_data(&self) -> Vec<u32> {
+        self.tree.gpu_data()
+    }
+
+    // Written expanded, as the tree it was made from
+    #[allow(unused)]
+    pub fn write(&self, writer: impl Write) -> Result<(), OctreeFileError> {
+        self.tree.write(writer)
+    }
+}
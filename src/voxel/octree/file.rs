@@ -0,0 +1,348 @@
+use super::{Node, Octree};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+const MAGIC: [u8; 4] = *b"SVOT";
+const VERSION: u32 = 2;
+
+const TAG_LEAF: u8 = 0;
+const TAG_BRANCH: u8 = 1;
+
+#[derive(Debug)]
+#[allow(unused)]
+pub enum OctreeFileError {
+    Io(io::Error),
+    Truncated,
+    BadMagic([u8; 4]),
+    UnsupportedVersion(u32),
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for OctreeFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "octree I/O error: {err}"),
+            Self::Truncated => write!(f, "octree data is truncated"),
+            Self::BadMagic(magic) => write!(f, "not an octree file (magic {magic:02x?})"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported octree file version {version}")
+            }
+            Self::Corrupt(what) => write!(f, "corrupt octree data: {what}"),
+        }
+    }
+}
+
+impl Error for OctreeFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for OctreeFileError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::Io(err),
+        }
+    }
+}
+
+// Layout, all integers little-endian:
+//   magic: [u8; 4], version: u32, log_extent: u32, node_count: u64,
+//   node_count nodes in breadth-first order, each a tag byte followed by
+//   a u64 voxel. Children of the k-th branch are the 8 nodes starting at
+//   1 + 8 * k, as laid out by `shrinked()`.
+// Version 1 has no voxels for branches; they are taken from the first child.
+impl Octree {
+    #[allow(unused)]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), OctreeFileError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        Ok(writer.flush()?)
+    }
+
+    #[allow(unused)]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, OctreeFileError> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> Result<(), OctreeFileError> {
+        let mut order = Vec::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(self.root);
+        while let Some(i_node) = queue.pop_front() {
+            order.push(i_node);
+            if self.nodes[i_node].is_branch() {
+                queue.extend(self.nodes[i_node].children);
+            }
+        }
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(self.log_extent as u32).to_le_bytes())?;
+        writer.write_all(&(order.len() as u64).to_le_bytes())?;
+        for i_node in order {
+            let node = &self.nodes[i_node];
+            let tag = if node.is_leaf() { TAG_LEAF } else { TAG_BRANCH };
+            writer.write_all(&[tag])?;
+            writer.write_all(&(node.voxel as u64).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read(mut reader: impl Read) -> Result<Self, OctreeFileError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(OctreeFileError::BadMagic(magic));
+        }
+        let version = read_u32(&mut reader)?;
+        if !(1..=VERSION).contains(&version) {
+            return Err(OctreeFileError::UnsupportedVersion(version));
+        }
+        let log_extent = read_u32(&mut reader)?;
+        if log_extent >= usize::BITS {
+            return Err(OctreeFileError::Corrupt("extent does not fit in usize"));
+        }
+        let log_extent = log_extent as usize;
+        let n_nodes = read_u64(&mut reader)?;
+        if n_nodes == 0 || n_nodes % 8 != 1 {
+            return Err(OctreeFileError::Corrupt("node count is not 8k + 1"));
+        }
+
+        let mut nodes = Vec::new();
+        let mut depths = vec![0];
+        let mut next_child = 1;
+        while (nodes.len() as u64) < n_nodes {
+            let i_node = nodes.len();
+            if i_node >= next_child {
+                return Err(OctreeFileError::Corrupt("fewer branches than nodes"));
+            }
+            let mut tag = [0];
+            reader.read_exact(&mut tag)?;
+            match tag[0] {
+                TAG_LEAF => {
+                    let voxel = read_u64(&mut reader)?;
+                    nodes.push(Node::leaf(voxel as usize));
+                }
+                TAG_BRANCH => {
+                    if depths[i_node] >= log_extent {
+                        return Err(OctreeFileError::Corrupt("branch below unit extent"));
+                    }
+                    if next_child + 8 > n_nodes as usize {
+                        return Err(OctreeFileError::Corrupt("more branches than nodes"));
+                    }
+                    let voxel = if version > 1 {
+                        read_u64(&mut reader)? as usize
+                    } else {
+                        !0
+                    };
+                    let mut node = Node::leaf(voxel);
+                    for (i_child, j_node) in node.children.iter_mut().enumerate() {
+                        *j_node = next_child + i_child;
+                    }
+                    next_child += 8;
+                    depths.resize(next_child, depths[i_node] + 1);
+                    nodes.push(node);
+                }
+                _ => return Err(OctreeFileError::Corrupt("unknown node tag")),
+            }
+        }
+        if next_child != nodes.len() {
+            return Err(OctreeFileError::Corrupt("fewer branches than nodes"));
+        }
+        if reader.read(&mut [0])? != 0 {
+            return Err(OctreeFileError::Corrupt("trailing data"));
+        }
+
+        // Edits merge branches of equal leaves, so no tree written has them
+        for node in nodes.iter().filter(|node| node.is_branch()) {
+            let first = nodes[node.children[0]];
+            if first.is_leaf() && node.children.iter().all(|&j_node| nodes[j_node] == first) {
+                return Err(OctreeFileError::Corrupt("branch of equal leaves"));
+            }
+        }
+        for i_node in (0..nodes.len()).rev() {
+            if version == 1 && nodes[i_node].is_branch() {
+                nodes[i_node].voxel = nodes[nodes[i_node].children[0]].voxel;
+            }
+        }
+        Ok(Self {
+            nodes,
+            free_nodes: Vec::new(),
+            dirty: Vec::new(),
+            subtree_hashes: RefCell::default(),
+            root: 0,
+            log_extent,
+        })
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::LodPolicy, *};
+
+    fn header(version: u32, log_extent: u32, n_nodes: u64) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&version.to_le_bytes());
+        data.extend_from_slice(&log_extent.to_le_bytes());
+        data.extend_from_slice(&n_nodes.to_le_bytes());
+        data
+    }
+
+    fn sample_tree() -> Octree {
+        let mut tree = Octree::with_log_extent(3);
+        tree.set([1, 2, 3], [3, 1, 2], 5);
+        tree.set([0, 0, 0], [1, 1, 1], 7);
+        tree
+    }
+
+    #[test]
+    fn round_trip() {
+        let tree = sample_tree();
+        let mut data = Vec::new();
+        tree.write(&mut data).unwrap();
+        assert_eq!(Octree::read(&data[..]).unwrap(), tree.shrinked());
+    }
+
+    // Branch voxels are chosen by `update_lod`, not derived from the leaves
+    #[test]
+    fn round_trip_lod() {
+        let mut tree = sample_tree();
+        tree.update_lod(LodPolicy::Majority);
+        let mut data = Vec::new();
+        tree.write(&mut data).unwrap();
+        let read = Octree::read(&data[..]).unwrap();
+        for level in 0..=3 {
+            assert_eq!(
+                read.coarsened(level),
+                tree.coarsened(level),
+                "level {level}"
+            );
+        }
+    }
+
+    #[test]
+    fn version_1() {
+        let mut data = header(1, 1, 9);
+        data.push(TAG_BRANCH);
+        for voxel in [3u64, 3, 3, 3, 3, 3, 3, 4] {
+            data.push(TAG_LEAF);
+            data.extend_from_slice(&voxel.to_le_bytes());
+        }
+        let mut tree = Octree::with_log_extent(1);
+        tree.set([0, 0, 0], [2, 2, 2], 3);
+        tree.set([1, 1, 1], [1, 1, 1], 4);
+        let read = Octree::read(&data[..]).unwrap();
+        assert_eq!(read, tree);
+        assert_eq!(read.coarsened(0), tree.coarsened(0));
+    }
+
+    #[test]
+    fn truncated() {
+        let mut data = Vec::new();
+        sample_tree().write(&mut data).unwrap();
+        for len in 0..data.len() {
+            assert!(
+                matches!(Octree::read(&data[..len]), Err(OctreeFileError::Truncated)),
+                "{len} bytes out of {}",
+                data.len(),
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt() {
+        // Leaf root followed by a branch nothing points to
+        let mut data = header(VERSION, 3, 9);
+        data.push(TAG_LEAF);
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.push(TAG_BRANCH);
+        assert!(matches!(
+            Octree::read(&data[..]),
+            Err(OctreeFileError::Corrupt(_))
+        ));
+
+        // Branch at unit extent
+        let mut data = header(VERSION, 0, 9);
+        data.push(TAG_BRANCH);
+        assert!(matches!(
+            Octree::read(&data[..]),
+            Err(OctreeFileError::Corrupt(_))
+        ));
+
+        let mut data = header(VERSION, 3, 2);
+        data.push(TAG_LEAF);
+        assert!(matches!(
+            Octree::read(&data[..]),
+            Err(OctreeFileError::Corrupt(_))
+        ));
+
+        let mut data = header(VERSION, 3, 1);
+        data.push(7);
+        assert!(matches!(
+            Octree::read(&data[..]),
+            Err(OctreeFileError::Corrupt(_))
+        ));
+
+        // Not canonical, the branch should have been a single leaf
+        let mut data = header(VERSION, 1, 9);
+        data.push(TAG_BRANCH);
+        data.extend_from_slice(&3u64.to_le_bytes());
+        for _ in 0..8 {
+            data.push(TAG_LEAF);
+            data.extend_from_slice(&3u64.to_le_bytes());
+        }
+        assert!(matches!(
+            Octree::read(&data[..]),
+            Err(OctreeFileError::Corrupt(_))
+        ));
+
+        let mut data = Vec::new();
+        sample_tree().write(&mut data).unwrap();
+        data.push(0);
+        assert!(matches!(
+            Octree::read(&data[..]),
+            Err(OctreeFileError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn wrong_version() {
+        let mut data = header(VERSION + 1, 3, 1);
+        data.push(TAG_LEAF);
+        data.extend_from_slice(&0u64.to_le_bytes());
+        assert!(matches!(
+            Octree::read(&data[..]),
+            Err(OctreeFileError::UnsupportedVersion(version)) if version == VERSION + 1
+        ));
+
+        data[..4].copy_from_slice(b"SVOX");
+        assert!(matches!(
+            Octree::read(&data[..]),
+            Err(OctreeFileError::BadMagic(magic)) if magic == *b"SVOX"
+        ));
+    }
+}
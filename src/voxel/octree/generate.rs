@@ -0,0 +1,276 @@
+use super::{child_offset, Node, Octree};
+use crate::math::{vec3, Vector};
+use std::cell::RefCell;
+
+pub trait Generator {
+    fn voxel(&self, offset: [usize; 3]) -> usize;
+
+    // Voxel shared by the whole box `offset + [0, extent)^3`, if it can be
+    // told without sampling every voxel. Subtrees are skipped when it is known.
+    fn uniform(&self, _offset: [usize; 3], _extent: usize) -> Option<usize> {
+        None
+    }
+}
+
+impl<F: Fn([usize; 3]) -> usize> Generator for F {
+    fn voxel(&self, offset: [usize; 3]) -> usize {
+        self(offset)
+    }
+}
+
+// Voxels with the centre at non-positive distance are filled.
+// The distance must never be overestimated, like in `Shape::sdf`.
+#[derive(Debug, Clone, Copy)]
+pub struct Sdf<F> {
+    pub sdf: F,
+    pub voxel: usize,
+}
+
+#[allow(unused)]
+pub fn sphere(center: vec3, radius: f32, voxel: usize) -> Sdf<impl Fn(vec3) -> f32> {
+    Sdf {
+        sdf: move |p: vec3| (p - center).length() - radius,
+        voxel,
+    }
+}
+
+// Fractal value noise in [-1, 1], each octave has half the wavelength of the previous one
+#[derive(Debug, Clone, Copy)]
+pub struct ValueNoise {
+    pub seed: u32,
+    pub octaves: u32,
+    pub wavelength: f32,
+    pub persistence: f32,
+}
+
+// Solid below `base + amplitude * noise(x, z)`, the top `surface_depth` voxels
+// of the column are `surface` and the rest is `fill`
+#[derive(Debug, Clone, Copy)]
+pub struct Heightmap {
+    pub noise: ValueNoise,
+    pub base: f32,
+    pub amplitude: f32,
+    pub surface: usize,
+    pub surface_depth: f32,
+    pub fill: usize,
+}
+
+// Carves out of `ground` everything where `noise(x, y, z) < threshold`
+#[derive(Debug, Clone, Copy)]
+pub struct Caves<G> {
+    pub ground: G,
+    pub noise: ValueNoise,
+    pub threshold: f32,
+}
+
+fn voxel_center(offset: [usize; 3]) -> vec3 {
+    Vector(offset).cast() + 0.5
+}
+
+// Centre of the voxel centres of a box and the distance from it to the farthest one
+fn box_center(offset: [usize; 3], extent: usize) -> (vec3, f32) {
+    let center = voxel_center(offset) + 0.5 * (extent - 1) as f32;
+    (center, 0.5 * 3f32.sqrt() * (extent - 1) as f32)
+}
+
+impl<F: Fn(vec3) -> f32> Generator for Sdf<F> {
+    fn voxel(&self, offset: [usize; 3]) -> usize {
+        if (self.sdf)(voxel_center(offset)) <= 0.0 {
+            self.voxel
+        } else {
+            !0
+        }
+    }
+
+    fn uniform(&self, offset: [usize; 3], extent: usize) -> Option<usize> {
+        let (center, radius) = box_center(offset, extent);
+        let d = (self.sdf)(center);
+        if d + radius <= 0.0 {
+            Some(self.voxel)
+        } else if d - radius > 0.0 {
+            Some(!0)
+        } else {
+            None
+        }
+    }
+}
+
+fn hash([x, y, z]: [i32; 3], seed: u32) -> f32 {
+    let mut h = seed.wrapping_mul(0x9E3779B9)
+        ^ (x as u32).wrapping_mul(0x85EBCA6B)
+        ^ (y as u32).wrapping_mul(0xC2B2AE35)
+        ^ (z as u32).wrapping_mul(0x27D4EB2F);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2C1B3C6D);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297A2D39);
+    h ^= h >> 15;
+    h as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+impl ValueNoise {
+    #[allow(unused)]
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            octaves: 4,
+            wavelength: 32.0,
+            persistence: 0.5,
+        }
+    }
+
+    pub fn sample(&self, p: vec3) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut amplitude_sum = 0.0;
+        let mut frequency = 1.0 / self.wavelength;
+        for octave in 0..self.octaves {
+            sum += amplitude * self.octave(p * frequency, self.seed.wrapping_add(octave));
+            amplitude_sum += amplitude;
+            amplitude *= self.persistence;
+            frequency *= 2.0;
+        }
+        sum / amplitude_sum
+    }
+
+    // Bound on how fast `sample` changes per unit of distance
+    pub fn lipschitz(&self) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut amplitude_sum = 0.0;
+        let mut frequency = 1.0 / self.wavelength;
+        for _ in 0..self.octaves {
+            // Smoothstep slope is at most 1.5 between values 2 apart, along each axis
+            sum += amplitude * 3.0 * 3f32.sqrt() * frequency;
+            amplitude_sum += amplitude;
+            amplitude *= self.persistence;
+            frequency *= 2.0;
+        }
+        sum / amplitude_sum
+    }
+
+    fn octave(&self, p: vec3, seed: u32) -> f32 {
+        let cell = p.0.map(|pi| pi.floor());
+        let t = [0, 1, 2].map(|i| {
+            let t = p.0[i] - cell[i];
+            t * t * (3.0 - 2.0 * t)
+        });
+        let cell = cell.map(|ci| ci as i32);
+        let mut out = 0.0;
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut lattice = cell;
+            for i in 0..3 {
+                if corner & (1 << i) != 0 {
+                    weight *= t[i];
+                    lattice[i] += 1;
+                } else {
+                    weight *= 1.0 - t[i];
+                }
+            }
+            out += weight * hash(lattice, seed);
+        }
+        out
+    }
+}
+
+impl Heightmap {
+    pub fn height(&self, x: f32, z: f32) -> f32 {
+        self.base + self.amplitude * self.noise.sample(Vector([x, 0.0, z]))
+    }
+}
+
+impl Generator for Heightmap {
+    fn voxel(&self, [x, y, z]: [usize; 3]) -> usize {
+        let h = self.height(x as f32 + 0.5, z as f32 + 0.5);
+        let y = y as f32 + 0.5;
+        if y >= h {
+            !0
+        } else if y >= h - self.surface_depth {
+            self.surface
+        } else {
+            self.fill
+        }
+    }
+
+    fn uniform(&self, [x, y, z]: [usize; 3], extent: usize) -> Option<usize> {
+        let (center, _) = box_center([x, y, z], extent);
+        let radius = 0.5 * 2f32.sqrt() * (extent - 1) as f32;
+        let h = self.height(center.x(), center.z());
+        let dh = self.amplitude.abs() * self.noise.lipschitz() * radius;
+        let y_lo = y as f32 + 0.5;
+        let y_hi = y_lo + (extent - 1) as f32;
+        if y_lo >= h + dh {
+            Some(!0)
+        } else if y_hi < h - dh - self.surface_depth {
+            Some(self.fill)
+        } else {
+            None
+        }
+    }
+}
+
+impl<G: Generator> Generator for Caves<G> {
+    fn voxel(&self, offset: [usize; 3]) -> usize {
+        if self.noise.sample(voxel_center(offset)) < self.threshold {
+            !0
+        } else {
+            self.ground.voxel(offset)
+        }
+    }
+
+    fn uniform(&self, offset: [usize; 3], extent: usize) -> Option<usize> {
+        let (center, radius) = box_center(offset, extent);
+        let density = self.noise.sample(center);
+        let spread = self.noise.lipschitz() * radius;
+        if density + spread < self.threshold {
+            Some(!0)
+        } else if density - spread >= self.threshold {
+            self.ground.uniform(offset, extent)
+        } else if self.ground.uniform(offset, extent) == Some(!0) {
+            Some(!0)
+        } else {
+            None
+        }
+    }
+}
+
+impl Octree {
+    #[allow(unused)]
+    pub fn generate(log_extent: usize, generator: &impl Generator) -> Self {
+        let mut self_ = Self {
+            nodes: vec![Node::default()],
+            free_nodes: Vec::new(),
+            dirty: Vec::new(),
+            subtree_hashes: RefCell::default(),
+            root: 0,
+            log_extent,
+        };
+        self_.generate_descend(generator, 0, [0; 3], log_extent);
+        self_.shrinked()
+    }
+
+    fn generate_descend(
+        &mut self,
+        generator: &impl Generator,
+        i_node: usize,
+        offset: [usize; 3],
+        node_log_extent: usize,
+    ) {
+        if let Some(voxel) = generator.uniform(offset, 1 << node_log_extent) {
+            self.nodes[i_node].voxel = voxel;
+            return;
+        }
+        if node_log_extent == 0 {
+            self.nodes[i_node].voxel = generator.voxel(offset);
+            return;
+        }
+        self.split_leaf(i_node);
+        for i_child in 0..8 {
+            let next_offset = child_offset(offset, node_log_extent, i_child);
+            let j_node = self.nodes[i_node].children[i_child];
+            self.generate_descend(generator, j_node, next_offset, node_log_extent - 1);
+        }
+        self.merge_branch(i_node);
+    }
+}
@@ -0,0 +1,159 @@
+use super::{child_offset, Octree};
+
+// Deeper trees cannot be addressed with `usize` coordinates anyway
+const MAX_DEPTH: usize = usize::BITS as usize;
+
+// Callbacks for a depth-first walk over the tree
+pub trait Visitor {
+    // Called before descending into a branch, returning `false` skips the whole subtree
+    fn enter_branch(&mut self, _offset: [usize; 3], _extent: usize) -> bool {
+        true
+    }
+
+    fn leaf(&mut self, offset: [usize; 3], extent: usize, voxel: usize);
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Frame {
+    i_node: usize,
+    offset: [usize; 3],
+    node_log_extent: usize,
+    next_child: usize,
+}
+
+// Depth-first iterator over `(offset, extent, voxel)` of the leaves.
+// Branches at `max_depth` are yielded as leaves with their own voxel.
+#[derive(Debug, Clone)]
+pub struct Leaves<'a> {
+    tree: &'a Octree,
+    lo: [usize; 3],
+    hi: [usize; 3],
+    max_depth: usize,
+    root_pending: bool,
+    stack: [Frame; MAX_DEPTH],
+    stack_len: usize,
+}
+
+impl Octree {
+    #[allow(unused)]
+    pub fn leaves(&self) -> Leaves<'_> {
+        Leaves {
+            tree: self,
+            lo: [0; 3],
+            hi: [usize::MAX; 3],
+            max_depth: usize::MAX,
+            root_pending: true,
+            stack: [Frame::default(); MAX_DEPTH],
+            stack_len: 0,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn visit(&self, visitor: &mut impl Visitor) {
+        self.visit_descend(visitor, self.root, [0; 3], self.log_extent);
+    }
+
+    fn visit_descend(
+        &self,
+        visitor: &mut impl Visitor,
+        i_node: usize,
+        offset: [usize; 3],
+        node_log_extent: usize,
+    ) {
+        let node = &self.nodes[i_node];
+        let extent = 1 << node_log_extent;
+        if node.is_leaf() {
+            visitor.leaf(offset, extent, node.voxel);
+            return;
+        }
+        if !visitor.enter_branch(offset, extent) {
+            return;
+        }
+        for i_child in 0..8 {
+            let next_offset = child_offset(offset, node_log_extent, i_child);
+            self.visit_descend(
+                visitor,
+                node.children[i_child],
+                next_offset,
+                node_log_extent - 1,
+            );
+        }
+    }
+}
+
+impl Leaves<'_> {
+    // Only yield leaves intersecting the box `offset + [0, extent)`
+    #[allow(unused)]
+    pub fn within(mut self, offset: [usize; 3], extent: [usize; 3]) -> Self {
+        self.lo = offset;
+        self.hi = [0, 1, 2].map(|i| offset[i].saturating_add(extent[i]));
+        self
+    }
+
+    #[allow(unused)]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // Either yields the node or pushes it to be descended into
+    fn enter(
+        &mut self,
+        i_node: usize,
+        offset: [usize; 3],
+        node_log_extent: usize,
+    ) -> Option<([usize; 3], usize, usize)> {
+        let extent = 1 << node_log_extent;
+        if (0..3).any(|i| self.hi[i] <= offset[i] || offset[i] + extent <= self.lo[i]) {
+            return None;
+        }
+        let node = &self.tree.nodes[i_node];
+        if node.is_leaf() || self.stack_len >= self.max_depth {
+            return Some((offset, extent, node.voxel));
+        }
+        self.stack[self.stack_len] = Frame {
+            i_node,
+            offset,
+            node_log_extent,
+            next_child: 0,
+        };
+        self.stack_len += 1;
+        None
+    }
+}
+
+impl Iterator for Leaves<'_> {
+    type Item = ([usize; 3], usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.root_pending {
+            self.root_pending = false;
+            let item = self.enter(self.tree.root, [0; 3], self.tree.log_extent);
+            if item.is_some() {
+                return item;
+            }
+        }
+        while self.stack_len > 0 {
+            let frame = &mut self.stack[self.stack_len - 1];
+            if frame.next_child == 8 {
+                self.stack_len -= 1;
+                continue;
+            }
+            let i_child = frame.next_child;
+            frame.next_child += 1;
+            let Frame {
+                i_node,
+                offset,
+                node_log_extent,
+                ..
+            } = *frame;
+            let j_node = self.tree.nodes[i_node].children[i_child];
+            let next_offset = child_offset(offset, node_log_extent, i_child);
+            let item = self.enter(j_node, next_offset, node_log_extent - 1);
+            if item.is_some() {
+                return item;
+            }
+        }
+        None
+    }
+}
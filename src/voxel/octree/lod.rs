@@ -0,0 +1,183 @@
+use super::{Node, Octree};
+use crate::voxel::VoxelInfo;
+use std::{cell::RefCell, collections::HashMap};
+
+// How a branch voxel is chosen from the voxels of its subtree
+#[allow(unused)]
+#[derive(Debug, Clone, Copy)]
+pub enum LodPolicy<'a> {
+    // Voxel covering the largest volume, empty space included
+    Majority,
+    // Voxel with the highest opacity, so thin solid features do not disappear.
+    // Empty space loses to any solid voxel.
+    MostOpaque(&'a [VoxelInfo]),
+    // Voxel of the subtree with the colour closest to its average colour, or
+    // empty if most of the subtree is empty. Voxels missing from the palette
+    // have no colour and are left out, unless there is nothing else.
+    AverageColor(&'a [VoxelInfo]),
+}
+
+impl LodPolicy<'_> {
+    fn choose(&self, volumes: &HashMap<usize, usize>) -> usize {
+        let majority = |&(&voxel, &volume): &(&usize, &usize)| (volume, !voxel);
+        match self {
+            Self::Majority => *volumes.iter().max_by_key(majority).unwrap().0,
+            Self::MostOpaque(palette) => {
+                let opacity = |voxel: usize| match palette.get(voxel) {
+                    Some(info) => info.opacity,
+                    None if voxel == !0 => -1.0,
+                    None => 0.0,
+                };
+                let (&voxel, _) = volumes
+                    .iter()
+                    .max_by(|a, b| {
+                        opacity(*a.0)
+                            .total_cmp(&opacity(*b.0))
+                            .then_with(|| majority(a).cmp(&majority(b)))
+                    })
+                    .unwrap();
+                voxel
+            }
+            Self::AverageColor(palette) => {
+                let mut color = [0.0; 3];
+                let mut solid_volume = 0;
+                let mut empty_volume = 0;
+                for (&voxel, &volume) in volumes {
+                    if voxel == !0 {
+                        empty_volume += volume;
+                    }
+                    let Some(info) = palette.get(voxel) else {
+                        continue;
+                    };
+                    for (c, d) in color.iter_mut().zip(info.diffuse_color) {
+                        *c += volume as f32 * d;
+                    }
+                    solid_volume += volume;
+                }
+                if empty_volume > solid_volume {
+                    return !0;
+                }
+                if solid_volume == 0 {
+                    return Self::Majority.choose(volumes);
+                }
+                color = color.map(|c| c / solid_volume as f32);
+                let distance = |info: &VoxelInfo| {
+                    let mut sum = 0.0;
+                    for (c, d) in color.iter().zip(info.diffuse_color) {
+                        sum += (c - d) * (c - d);
+                    }
+                    sum
+                };
+                let (voxel, _) = volumes
+                    .keys()
+                    .filter_map(|&voxel| Some((voxel, palette.get(voxel)?)))
+                    .min_by(|a, b| distance(a.1).total_cmp(&distance(b.1)).then(a.0.cmp(&b.0)))
+                    .unwrap();
+                voxel
+            }
+        }
+    }
+}
+
+impl Octree {
+    // Replaces branch voxels, which `sample` returns for coarse levels.
+    // They are kept by `write` and `read`, but edits reset the branches they
+    // touch to the voxel of the first child, so call it again after editing.
+    #[allow(unused)]
+    pub fn update_lod(&mut self, policy: LodPolicy) {
+        self.update_lod_descend(&policy, self.root, self.log_extent);
+    }
+
+    // Tree with everything below `depth` levels from the root collapsed into
+    // leaves with the voxel of their branch
+    #[allow(unused)]
+    pub fn coarsened(&self, depth: usize) -> Self {
+        let mut out = Self {
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            dirty: Vec::new(),
+            subtree_hashes: RefCell::default(),
+            root: 0,
+            log_extent: self.log_extent,
+        };
+        let root = self.coarsened_descend(&mut out, self.root, depth);
+        out.nodes.push(root);
+        out.root = out.nodes.len() - 1;
+        out.shrinked()
+    }
+
+    fn update_lod_descend(
+        &mut self,
+        policy: &LodPolicy,
+        i_node: usize,
+        node_log_extent: usize,
+    ) -> HashMap<usize, usize> {
+        let node = self.nodes[i_node];
+        if node.is_leaf() {
+            return HashMap::from([(node.voxel, 1 << (3 * node_log_extent))]);
+        }
+        let mut volumes = HashMap::new();
+        for j_node in node.children {
+            let child_volumes = self.update_lod_descend(policy, j_node, node_log_extent - 1);
+            for (voxel, volume) in child_volumes {
+                *volumes.entry(voxel).or_insert(0) += volume;
+            }
+        }
+        self.nodes[i_node].voxel = policy.choose(&volumes);
+        self.mark_dirty(i_node);
+        volumes
+    }
+
+    fn coarsened_descend(&self, out: &mut Self, i_node: usize, depth: usize) -> Node {
+        let node = self.nodes[i_node];
+        if node.is_leaf() || depth == 0 {
+            return Node::leaf(node.voxel);
+        }
+        let children = node
+            .children
+            .map(|j_node| self.coarsened_descend(out, j_node, depth - 1));
+        if children
+            .iter()
+            .all(|child| child.is_leaf() && child.voxel == children[0].voxel)
+        {
+            return Node::leaf(children[0].voxel);
+        }
+        Node {
+            voxel: node.voxel,
+            children: children.map(|child| {
+                out.nodes.push(child);
+                out.nodes.len() - 1
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(diffuse_color: [f32; 3]) -> VoxelInfo {
+        VoxelInfo {
+            diffuse_color,
+            ..VoxelInfo::default()
+        }
+    }
+
+    #[test]
+    fn average_color() {
+        let palette = [
+            info([1.0, 0.0, 0.0]),
+            info([0.0, 0.0, 1.0]),
+            info([0.9, 0.1, 0.0]),
+        ];
+        let policy = LodPolicy::AverageColor(&palette);
+        // Only voxels of the subtree are candidates, however close the others are
+        assert_eq!(policy.choose(&HashMap::from([(0, 3), (1, 1)])), 0);
+        assert_eq!(policy.choose(&HashMap::from([(1, 3), (2, 1)])), 1);
+        // Voxels missing from the palette count as neither solid nor empty
+        assert_eq!(policy.choose(&HashMap::from([(!0, 2), (1, 1), (7, 5)])), !0);
+        assert_eq!(policy.choose(&HashMap::from([(!0, 1), (1, 2), (7, 5)])), 1);
+        assert_eq!(policy.choose(&HashMap::from([(!0, 1), (7, 5)])), !0);
+        assert_eq!(policy.choose(&HashMap::from([(7, 5), (8, 2)])), 7);
+    }
+}
@@ -0,0 +1,351 @@
+use super::{child_offset, Node, Octree};
+use std::{
+    array,
+    cell::RefCell,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    ptr,
+};
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeOp {
+    // Voxels of the first tree, and of the second one where the first is empty
+    Over,
+    // Voxels of the first tree where the second one is empty
+    Minus,
+}
+
+// Node of a tree as seen from a level of a walk over two trees. The smaller
+// tree is padded with empty space up to the extent of the larger one, so its
+// root sits in child 0 of `Padding` views.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Branch(usize),
+    Uniform(usize),
+    Padding,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    None,
+    // Some boxes are already reported
+    Partial,
+    All,
+}
+
+impl MergeOp {
+    fn apply(self, a: usize, b: usize) -> usize {
+        match self {
+            Self::Over if a != !0 => a,
+            Self::Over => b,
+            Self::Minus if b == !0 => a,
+            Self::Minus => !0,
+        }
+    }
+}
+
+// Trees are aligned at the origin and may have different extents, the result
+// has the larger one. Walks stop at uniform nodes wherever the other side
+// does not matter, and at subtrees with the same contents on both sides.
+// Those are told by hashes cached on the trees, so walking trees compared
+// before costs about as much as the regions edited since.
+impl Octree {
+    #[allow(unused)]
+    pub fn merged(&self, other: &Self, op: MergeOp) -> Self {
+        let log_extent = self.log_extent.max(other.log_extent);
+        let mut out = Self {
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            dirty: Vec::new(),
+            subtree_hashes: RefCell::default(),
+            root: 0,
+            log_extent,
+        };
+        let root = self.merge_descend(
+            self.view_root(log_extent),
+            other,
+            other.view_root(log_extent),
+            log_extent,
+            op,
+            &mut out,
+        );
+        out.nodes.push(root);
+        out.root = out.nodes.len() - 1;
+        out.shrinked()
+    }
+
+    // Cubes `(offset, extent)` covering every voxel which differs between the trees
+    #[allow(unused)]
+    pub fn diff(&self, other: &Self) -> Vec<([usize; 3], usize)> {
+        let log_extent = self.log_extent.max(other.log_extent);
+        let mut out = Vec::new();
+        let change = self.diff_descend(
+            self.view_root(log_extent),
+            other,
+            other.view_root(log_extent),
+            [0; 3],
+            log_extent,
+            &mut out,
+        );
+        if change == Change::All {
+            out.push(([0; 3], 1 << log_extent));
+        }
+        out
+    }
+
+    // Same extent, shape and leaf voxels, whatever the node order in memory.
+    // Unlike `==` ignores branch voxels and freed nodes, and unlike an empty
+    // `diff` tells apart trees with unmerged leaves.
+    #[allow(unused)]
+    pub fn structurally_eq(&self, other: &Self) -> bool {
+        self.log_extent == other.log_extent && self.eq_descend(self.root, other, other.root)
+    }
+
+    // Equal for trees with the same extent and voxels, and cheap to take
+    // again after small edits
+    #[allow(unused)]
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.log_extent.hash(&mut hasher);
+        self.subtree_hash(self.root).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn view_of(&self, i_node: usize) -> View {
+        let node = &self.nodes[i_node];
+        if node.is_leaf() {
+            View::Uniform(node.voxel)
+        } else {
+            View::Branch(i_node)
+        }
+    }
+
+    fn view_root(&self, log_extent: usize) -> View {
+        if log_extent > self.log_extent {
+            View::Padding
+        } else {
+            self.view_of(self.root)
+        }
+    }
+
+    fn view_child(&self, view: View, node_log_extent: usize, i_child: usize) -> View {
+        match view {
+            View::Branch(i_node) => self.view_of(self.nodes[i_node].children[i_child]),
+            View::Uniform(voxel) => View::Uniform(voxel),
+            View::Padding if i_child != 0 => View::Uniform(!0),
+            View::Padding => self.view_root(node_log_extent - 1),
+        }
+    }
+
+    fn is_same_node(&self, a: View, other: &Self, b: View) -> bool {
+        match (a, b) {
+            (View::Branch(i), View::Branch(j)) if ptr::eq(self, other) && i == j => true,
+            (View::Branch(i), View::Branch(j)) => self.subtree_hash(i) == other.subtree_hash(j),
+            _ => false,
+        }
+    }
+
+    fn subtree_hash(&self, i_node: usize) -> u64 {
+        let mut hashes = self.subtree_hashes.borrow_mut();
+        if hashes.len() < self.nodes.len() {
+            hashes.resize(self.nodes.len(), 0);
+        }
+        self.subtree_hash_descend(&mut hashes, i_node)
+    }
+
+    // Branch voxels are left out, they are derived from the leaves
+    fn subtree_hash_descend(&self, hashes: &mut [u64], i_node: usize) -> u64 {
+        if hashes[i_node] != 0 {
+            return hashes[i_node];
+        }
+        let node = self.nodes[i_node];
+        let mut hasher = DefaultHasher::new();
+        node.is_leaf().hash(&mut hasher);
+        if node.is_leaf() {
+            node.voxel.hash(&mut hasher);
+        } else {
+            for j_node in node.children {
+                self.subtree_hash_descend(hashes, j_node).hash(&mut hasher);
+            }
+        }
+        hashes[i_node] = hasher.finish().max(1);
+        hashes[i_node]
+    }
+
+    fn merge_descend(
+        &self,
+        a: View,
+        other: &Self,
+        b: View,
+        node_log_extent: usize,
+        op: MergeOp,
+        out: &mut Self,
+    ) -> Node {
+        match (op, a, b) {
+            (_, View::Uniform(x), View::Uniform(y)) => return Node::leaf(op.apply(x, y)),
+            (MergeOp::Over, View::Uniform(x), _) if x != !0 => return Node::leaf(x),
+            (MergeOp::Over, View::Uniform(_), _) => {
+                return other.copy_descend(b, node_log_extent, out)
+            }
+            (_, _, View::Uniform(y)) if y == !0 => {
+                return self.copy_descend(a, node_log_extent, out)
+            }
+            (MergeOp::Minus, View::Uniform(x), _) if x == !0 => return Node::leaf(!0),
+            (MergeOp::Minus, _, View::Uniform(_)) => return Node::leaf(!0),
+            _ => {}
+        }
+        if self.is_same_node(a, other, b) {
+            return match op {
+                MergeOp::Over => self.copy_descend(a, node_log_extent, out),
+                MergeOp::Minus => Node::leaf(!0),
+            };
+        }
+        let children: [Node; 8] = std::array::from_fn(|i_child| {
+            self.merge_descend(
+                self.view_child(a, node_log_extent, i_child),
+                other,
+                other.view_child(b, node_log_extent, i_child),
+                node_log_extent - 1,
+                op,
+                out,
+            )
+        });
+        out.push_branch(children)
+    }
+
+    fn copy_descend(&self, view: View, node_log_extent: usize, out: &mut Self) -> Node {
+        if let View::Uniform(voxel) = view {
+            return Node::leaf(voxel);
+        }
+        let children: [Node; 8] = std::array::from_fn(|i_child| {
+            let child = self.view_child(view, node_log_extent, i_child);
+            self.copy_descend(child, node_log_extent - 1, out)
+        });
+        out.push_branch(children)
+    }
+
+    // Branch with the children stored, or a leaf if they are all the same leaf
+    fn push_branch(&mut self, children: [Node; 8]) -> Node {
+        if children
+            .iter()
+            .all(|child| child.is_leaf() && child.voxel == children[0].voxel)
+        {
+            return Node::leaf(children[0].voxel);
+        }
+        Node {
+            voxel: children[0].voxel,
+            children: children.map(|child| {
+                self.nodes.push(child);
+                self.nodes.len() - 1
+            }),
+        }
+    }
+
+    fn diff_descend(
+        &self,
+        a: View,
+        other: &Self,
+        b: View,
+        offset: [usize; 3],
+        node_log_extent: usize,
+        out: &mut Vec<([usize; 3], usize)>,
+    ) -> Change {
+        if let (View::Uniform(x), View::Uniform(y)) = (a, b) {
+            return if x == y { Change::None } else { Change::All };
+        }
+        if self.is_same_node(a, other, b) {
+            return Change::None;
+        }
+        let half_extent = 1 << (node_log_extent - 1);
+        let mut changes = [Change::None; 8];
+        let offsets: [_; 8] =
+            array::from_fn(|i_child| child_offset(offset, node_log_extent, i_child));
+        for i_child in 0..8 {
+            changes[i_child] = self.diff_descend(
+                self.view_child(a, node_log_extent, i_child),
+                other,
+                other.view_child(b, node_log_extent, i_child),
+                offsets[i_child],
+                node_log_extent - 1,
+                out,
+            );
+        }
+        // Fully changed children are reported here so that they can be
+        // coalesced into their parent
+        if changes.iter().all(|&change| change == Change::All) {
+            return Change::All;
+        }
+        let mut change = Change::None;
+        for i_child in 0..8 {
+            if changes[i_child] == Change::All {
+                out.push((offsets[i_child], half_extent));
+            }
+            if changes[i_child] != Change::None {
+                change = Change::Partial;
+            }
+        }
+        change
+    }
+
+    fn eq_descend(&self, i_node: usize, other: &Self, j_node: usize) -> bool {
+        if ptr::eq(self, other) && i_node == j_node {
+            return true;
+        }
+        let a = &self.nodes[i_node];
+        let b = &other.nodes[j_node];
+        match (a.is_leaf(), b.is_leaf()) {
+            (true, true) => a.voxel == b.voxel,
+            (false, false) => (0..8).all(|i| self.eq_descend(a.children[i], other, b.children[i])),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next_random(state: &mut u64) -> usize {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state as usize
+    }
+
+    // Hashes cached by earlier walks have to follow later edits of either tree
+    #[test]
+    fn diff_after_edits() {
+        let mut state = 0x2545_F491_4F6C_DD1D;
+        let mut a = Octree::with_log_extent(4);
+        a.set([0, 0, 0], [16, 5, 16], 1);
+        a.set([3, 4, 7], [6, 6, 6], 2);
+        let mut b = a.clone();
+        assert!(a.diff(&b).is_empty());
+        for _ in 0..100 {
+            let tree = if next_random(&mut state) % 4 == 3 {
+                &mut a
+            } else {
+                &mut b
+            };
+            let offset = [0; 3].map(|_| next_random(&mut state) % 16);
+            let extent = offset.map(|o| 1 + next_random(&mut state) % (16 - o).min(4));
+            let voxel = [!0, 1, 2][next_random(&mut state) % 3];
+            tree.set(offset, extent, voxel);
+
+            let diff = a.diff(&b);
+            for x in 0..16 {
+                for y in 0..16 {
+                    for z in 0..16 {
+                        let p = [x, y, z];
+                        let in_diff = diff
+                            .iter()
+                            .any(|(o, e)| (0..3).all(|i| o[i] <= p[i] && p[i] < o[i] + e));
+                        assert_eq!(in_diff, a.get(p) != b.get(p), "{p:?}");
+                    }
+                }
+            }
+            assert_eq!(diff.is_empty(), a.structurally_eq(&b));
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use super::{child_offset, vertex_index, Octree};
+use crate::Vertex;
+use std::collections::{BTreeSet, HashMap};
+
+// Axes spanning a face perpendicular to the given one, in the order `debug_mesh` uses
+const FACE_AXES: [[usize; 2]; 3] = [[1, 2], [0, 2], [0, 1]];
+
+// Normal index as in `vertex_index`, for the negative and positive face along an axis
+const NORMAL_INDEX: [[usize; 2]; 3] = [[4, 5], [2, 3], [0, 1]];
+
+#[derive(Debug, Clone, Copy)]
+struct PlaneLeaf {
+    offset: [usize; 3],
+    extent: usize,
+    voxel: usize,
+    // Whether the leaf lies on the positive side of the plane
+    above: bool,
+}
+
+impl Octree {
+    // Only faces between a filled voxel and an empty one are emitted,
+    // and coplanar faces with the same voxel are merged into larger quads.
+    #[allow(unused)]
+    pub fn greedy_mesh(&self) -> (Vec<u32>, Vec<Vertex>) {
+        let mut indices = Vec::new();
+        let mut vertices = Vec::new();
+        let mut index_of = HashMap::new();
+
+        let mut planes = [BTreeSet::new(), BTreeSet::new(), BTreeSet::new()];
+        for (offset, extent, voxel) in self.leaves() {
+            if voxel == !0 {
+                continue;
+            }
+            for (axis, plane) in planes.iter_mut().enumerate() {
+                plane.insert(offset[axis]);
+                plane.insert(offset[axis] + extent);
+            }
+        }
+
+        let mut leaves = Vec::new();
+        for (axis, plane) in planes.iter().enumerate() {
+            for &c in plane {
+                leaves.clear();
+                self.plane_leaves(&mut leaves, axis, c, self.root, [0; 3], self.log_extent);
+                let [mask_neg, mask_pos, us, vs] = plane_masks(&leaves, axis);
+                for (mask, positive) in [(mask_neg, false), (mask_pos, true)] {
+                    for [i0, j0, i1, j1, voxel] in greedy_rects(&mask, us.len().saturating_sub(1)) {
+                        let [u, v] = FACE_AXES[axis];
+                        let normal = NORMAL_INDEX[axis][positive as usize];
+                        let mut corners = [[0; 5]; 4];
+                        for (k, corner) in corners.iter_mut().enumerate() {
+                            corner[axis] = c;
+                            corner[u] = if k & 1 == 0 { us[i0] } else { us[i1] };
+                            corner[v] = if k & 2 == 0 { vs[j0] } else { vs[j1] };
+                            corner[3] = normal;
+                            corner[4] = voxel;
+                        }
+                        let [i, ii, iii, iv] = corners
+                            .map(|key| vertex_index(&mut index_of, &mut vertices, key) as u32);
+                        // Same winding as the corresponding face in `debug_mesh`
+                        if normal == 1 || normal == 2 || normal == 5 {
+                            indices.extend_from_slice(&[i, ii, iv, iv, iii, i]);
+                        } else {
+                            indices.extend_from_slice(&[i, iii, iv, iv, ii, i]);
+                        }
+                    }
+                }
+            }
+        }
+
+        (indices, vertices)
+    }
+
+    fn plane_leaves(
+        &self,
+        acc: &mut Vec<PlaneLeaf>,
+        axis: usize,
+        c: usize,
+        i_node: usize,
+        offset: [usize; 3],
+        node_log_extent: usize,
+    ) {
+        let extent = 1 << node_log_extent;
+        if c < offset[axis] || c > offset[axis] + extent {
+            return;
+        }
+        let node = &self.nodes[i_node];
+        if node.is_leaf() {
+            if c == offset[axis] || c == offset[axis] + extent {
+                acc.push(PlaneLeaf {
+                    offset,
+                    extent,
+                    voxel: node.voxel,
+                    above: c == offset[axis],
+                });
+            }
+            return;
+        }
+        for i_child in 0..8 {
+            let next_offset = child_offset(offset, node_log_extent, i_child);
+            self.plane_leaves(
+                acc,
+                axis,
+                c,
+                node.children[i_child],
+                next_offset,
+                node_log_extent - 1,
+            );
+        }
+    }
+}
+
+// Voxels of faces looking towards negative and positive axis direction,
+// over a grid compressed to the leaf boundaries within the plane
+fn plane_masks(leaves: &[PlaneLeaf], axis: usize) -> [Vec<usize>; 4] {
+    let [u, v] = FACE_AXES[axis];
+    let mut us = BTreeSet::new();
+    let mut vs = BTreeSet::new();
+    for leaf in leaves {
+        us.insert(leaf.offset[u]);
+        us.insert(leaf.offset[u] + leaf.extent);
+        vs.insert(leaf.offset[v]);
+        vs.insert(leaf.offset[v] + leaf.extent);
+    }
+    let us: Vec<_> = us.into_iter().collect();
+    let vs: Vec<_> = vs.into_iter().collect();
+    let nu = us.len().saturating_sub(1);
+    let nv = vs.len().saturating_sub(1);
+
+    let mut below = vec![!0; nu * nv];
+    let mut above = vec![!0; nu * nv];
+    for leaf in leaves {
+        let i0 = us.partition_point(|&x| x < leaf.offset[u]);
+        let i1 = us.partition_point(|&x| x < leaf.offset[u] + leaf.extent);
+        let j0 = vs.partition_point(|&x| x < leaf.offset[v]);
+        let j1 = vs.partition_point(|&x| x < leaf.offset[v] + leaf.extent);
+        let side = if leaf.above { &mut above } else { &mut below };
+        for j in j0..j1 {
+            side[j * nu + i0..j * nu + i1].fill(leaf.voxel);
+        }
+    }
+
+    let mut mask_neg = vec![!0; nu * nv];
+    let mut mask_pos = vec![!0; nu * nv];
+    for i in 0..nu * nv {
+        if below[i] != !0 && above[i] == !0 {
+            mask_pos[i] = below[i];
+        }
+        if above[i] != !0 && below[i] == !0 {
+            mask_neg[i] = above[i];
+        }
+    }
+    [mask_neg, mask_pos, us, vs]
+}
+
+// Rectangles `[i0, j0, i1, j1, voxel]` covering all non-empty cells of a row-major mask
+fn greedy_rects(mask: &[usize], nu: usize) -> Vec<[usize; 5]> {
+    let mut out = Vec::new();
+    if nu == 0 {
+        return out;
+    }
+    let nv = mask.len() / nu;
+    let mut used = vec![false; mask.len()];
+    for j0 in 0..nv {
+        let mut i0 = 0;
+        while i0 < nu {
+            let voxel = mask[j0 * nu + i0];
+            if voxel == !0 || used[j0 * nu + i0] {
+                i0 += 1;
+                continue;
+            }
+            let fits = |i: usize, j: usize| mask[j * nu + i] == voxel && !used[j * nu + i];
+            let mut i1 = i0 + 1;
+            while i1 < nu && fits(i1, j0) {
+                i1 += 1;
+            }
+            let mut j1 = j0 + 1;
+            while j1 < nv && (i0..i1).all(|i| fits(i, j1)) {
+                j1 += 1;
+            }
+            for j in j0..j1 {
+                used[j * nu + i0..j * nu + i1].fill(true);
+            }
+            out.push([i0, j0, i1, j1, voxel]);
+            i0 = i1;
+        }
+    }
+    out
+}
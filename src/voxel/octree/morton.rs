@@ -0,0 +1,66 @@
+use super::{Node, Octree};
+use crate::voxel_bintree::{expand_bits_3, shrink_bits_3};
+use std::cell::RefCell;
+
+pub fn morton_3([x, y, z]: [usize; 3]) -> usize {
+    (expand_bits_3(x as _) | (expand_bits_3(y as _) << 1) | (expand_bits_3(z as _) << 2)) as _
+}
+
+pub fn unmorton_3(code: usize) -> [usize; 3] {
+    [0, 1, 2].map(|i| shrink_bits_3((code >> i) as _) as _)
+}
+
+impl Octree {
+    // Bulk load of voxels already in Z-order, index `i` goes to `unmorton_3(i)`,
+    // so every octant is a contiguous subslice
+    pub fn from_morton(voxels: &[usize]) -> Self {
+        let mut log_extent = 0;
+        while 1 << (3 * log_extent) < voxels.len() {
+            log_extent += 1;
+        }
+        if 1 << (3 * log_extent) > voxels.len() {
+            panic!("Array size is not a precise 8^n");
+        }
+        let mut self_ = Self {
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            dirty: Vec::new(),
+            subtree_hashes: RefCell::default(),
+            root: 0,
+            log_extent,
+        };
+        let root = self_.morton_descend(voxels);
+        self_.nodes.push(root);
+        self_.root = self_.nodes.len() - 1;
+        self_.shrinked()
+    }
+
+    #[allow(unused)]
+    pub fn get_morton(&self, code: usize) -> usize {
+        self.get(unmorton_3(code))
+    }
+
+    fn morton_descend(&mut self, voxels: &[usize]) -> Node {
+        if voxels.len() == 1 {
+            return Node::leaf(voxels[0]);
+        }
+        let child_len = voxels.len() / 8;
+        let children: [Node; 8] = std::array::from_fn(|i_child| {
+            self.morton_descend(&voxels[i_child * child_len..(i_child + 1) * child_len])
+        });
+        let voxel = children[0].voxel;
+        if children
+            .iter()
+            .all(|child| child.is_leaf() && child.voxel == voxel)
+        {
+            return Node::leaf(voxel);
+        }
+        Node {
+            voxel,
+            children: children.map(|child| {
+                self.nodes.push(child);
+                self.nodes.len() - 1
+            }),
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use super::{child_offset, Octree};
+use crate::math::{vec3, Vector};
+
+// First solid leaf touched by a moving shape. `time` is the fraction of the
+// motion travelled before the contact, `normal` points out of the leaf and is
+// zero if the shape overlaps the leaf from the start.
+#[derive(Debug, Clone, Copy)]
+#[allow(unused)]
+pub struct SweepHit {
+    pub time: f32,
+    pub normal: vec3,
+    pub offset: [usize; 3],
+    pub extent: usize,
+    pub voxel: usize,
+}
+
+// Everything is measured in voxels of the tree, outside of it is empty space.
+// Boxes are open, so shapes resting on or sliding along a face do not collide.
+impl Octree {
+    #[allow(unused)]
+    pub fn is_solid(&self, p: vec3) -> bool {
+        if p.0.iter().any(|&x| x < 0.0) {
+            return false;
+        }
+        match self.leaf_at(p.0.map(|x| x as usize)) {
+            Some((_, _, voxel)) => voxel != !0,
+            None => false,
+        }
+    }
+
+    // Solid leaves overlapping the box `[lo, hi]` as `(offset, extent, voxel)`
+    #[allow(unused)]
+    pub fn overlapping_leaves(
+        &self,
+        lo: vec3,
+        hi: vec3,
+    ) -> impl Iterator<Item = ([usize; 3], usize, usize)> + '_ {
+        let offset = lo.0.map(|x| x.max(0.0).floor() as usize);
+        let end = hi.0.map(|x| x.max(0.0).ceil() as usize);
+        self.leaves()
+            .within(offset, [0, 1, 2].map(|i| end[i].saturating_sub(offset[i])))
+            .filter(move |&(offset, extent, voxel)| {
+                voxel != !0
+                    && (0..3).all(|i| {
+                        (offset[i] as f32) < hi.0[i] && lo.0[i] < (offset[i] + extent) as f32
+                    })
+            })
+    }
+
+    // Box `[lo, hi]` moved by `motion`
+    #[allow(unused)]
+    pub fn sweep_aabb(&self, lo: vec3, hi: vec3, motion: vec3) -> Option<SweepHit> {
+        let size = hi - lo;
+        self.sweep(lo, motion, size, Vector([0.0; 3]), |leaf_lo, leaf_hi| {
+            sweep_point_box(lo, motion, leaf_lo - size, leaf_hi)
+        })
+    }
+
+    #[allow(unused)]
+    pub fn sweep_sphere(&self, center: vec3, radius: f32, motion: vec3) -> Option<SweepHit> {
+        let margin = Vector([radius; 3]);
+        self.sweep(center, motion, margin, margin, |leaf_lo, leaf_hi| {
+            sweep_sphere_box(center, radius, motion, leaf_lo, leaf_hi)
+        })
+    }
+
+    // Descends into nodes which the point `origin` moving by `motion` enters
+    // once grown by `margin_lo` and `margin_hi`, nearer ones first and only
+    // while they can still beat the best hit found
+    fn sweep(
+        &self,
+        origin: vec3,
+        motion: vec3,
+        margin_lo: vec3,
+        margin_hi: vec3,
+        leaf_test: impl Fn(vec3, vec3) -> Option<(f32, vec3)>,
+    ) -> Option<SweepHit> {
+        let mut mirror = 0;
+        for i in 0..3 {
+            if motion.0[i] < 0.0 {
+                mirror |= 1 << i;
+            }
+        }
+        let mut best = None;
+        let mut stack = vec![(self.root, [0; 3], self.log_extent)];
+        while let Some((i_node, offset, node_log_extent)) = stack.pop() {
+            let extent = 1 << node_log_extent;
+            let lo = Vector(offset.map(|x| x as f32));
+            let hi = Vector(offset.map(|x| (x + extent) as f32));
+            let Some((t_node, _)) = sweep_point_box(origin, motion, lo - margin_lo, hi + margin_hi)
+            else {
+                continue;
+            };
+            if matches!(best, Some(SweepHit { time, .. }) if time <= t_node) {
+                continue;
+            }
+            let node = &self.nodes[i_node];
+            if node.is_leaf() {
+                if node.voxel == !0 {
+                    continue;
+                }
+                if let Some((time, normal)) = leaf_test(lo, hi) {
+                    if !matches!(best, Some(SweepHit { time: t, .. }) if t <= time) {
+                        best = Some(SweepHit {
+                            time,
+                            normal,
+                            offset,
+                            extent,
+                            voxel: node.voxel,
+                        });
+                    }
+                }
+                continue;
+            }
+            for i in (0..8).rev() {
+                let i_child = i ^ mirror;
+                let next_offset = child_offset(offset, node_log_extent, i_child);
+                stack.push((node.children[i_child], next_offset, node_log_extent - 1));
+            }
+        }
+        best
+    }
+}
+
+// Turns the parameter range during which a moving point is inside of a shape
+// into the time of contact within `[0, 1]`, the normal is dropped on overlap
+fn contact(t_enter: f32, t_exit: f32, normal: vec3) -> Option<(f32, vec3)> {
+    if t_enter >= t_exit || t_exit <= 0.0 || t_enter > 1.0 {
+        None
+    } else if t_enter < 0.0 {
+        Some((0.0, Vector([0.0; 3])))
+    } else {
+        Some((t_enter, normal))
+    }
+}
+
+fn sweep_point_box(origin: vec3, motion: vec3, lo: vec3, hi: vec3) -> Option<(f32, vec3)> {
+    let mut t_enter = f32::NEG_INFINITY;
+    let mut t_exit = f32::INFINITY;
+    let mut normal = Vector([0.0; 3]);
+    for i in 0..3 {
+        let o = origin.0[i];
+        let d = motion.0[i];
+        if d == 0.0 {
+            if o <= lo.0[i] || o >= hi.0[i] {
+                return None;
+            }
+            continue;
+        }
+        let (near, far, sign) = if d > 0.0 {
+            (lo.0[i], hi.0[i], -1.0)
+        } else {
+            (hi.0[i], lo.0[i], 1.0)
+        };
+        let t_near = (near - o) / d;
+        if t_near > t_enter {
+            t_enter = t_near;
+            normal = Vector([0.0; 3]);
+            normal.0[i] = sign;
+        }
+        t_exit = t_exit.min((far - o) / d);
+    }
+    contact(t_enter, t_exit, normal)
+}
+
+// Moving point against a ball, or against an infinite cylinder if `axis`
+// is given, in which case the coordinate along it is ignored
+fn sweep_point_round(
+    origin: vec3,
+    motion: vec3,
+    center: vec3,
+    radius: f32,
+    axis: Option<usize>,
+) -> Option<(f32, vec3)> {
+    let mut oc = origin - center;
+    let mut m = motion;
+    if let Some(k) = axis {
+        oc.0[k] = 0.0;
+        m.0[k] = 0.0;
+    }
+    let c = oc.dot(oc) - radius * radius;
+    let a = m.dot(m);
+    if a == 0.0 {
+        return (c < 0.0).then_some((0.0, Vector([0.0; 3])));
+    }
+    let b = oc.dot(m);
+    let discriminant = b * b - a * c;
+    if discriminant <= 0.0 {
+        return None;
+    }
+    let sq = discriminant.sqrt();
+    let t_enter = (-b - sq) / a;
+    contact(t_enter, (-b + sq) / a, (oc + m * t_enter) / radius)
+}
+
+// The box grown by the radius is the union of the box grown along each axis,
+// a cylinder along each edge and a ball at each corner, so the contact is the
+// earliest of theirs
+fn sweep_sphere_box(
+    center: vec3,
+    radius: f32,
+    motion: vec3,
+    lo: vec3,
+    hi: vec3,
+) -> Option<(f32, vec3)> {
+    let mut best: Option<(f32, vec3)> = None;
+    let mut consider = |hit: Option<(f32, vec3)>| {
+        if let Some(hit) = hit {
+            if best.is_none_or(|b| hit.0 < b.0) {
+                best = Some(hit);
+            }
+        }
+    };
+    for k in 0..3 {
+        let mut grown_lo = lo;
+        let mut grown_hi = hi;
+        grown_lo.0[k] -= radius;
+        grown_hi.0[k] += radius;
+        consider(sweep_point_box(center, motion, grown_lo, grown_hi));
+    }
+    for i_corner in 0..8 {
+        let corner = Vector([0, 1, 2].map(|i| {
+            if i_corner & (1 << i) != 0 {
+                hi.0[i]
+            } else {
+                lo.0[i]
+            }
+        }));
+        consider(sweep_point_round(center, motion, corner, radius, None));
+        // Each edge once, from its lower corner
+        for k in 0..3 {
+            if i_corner & (1 << k) != 0 {
+                continue;
+            }
+            let hit = sweep_point_round(center, motion, corner, radius, Some(k));
+            let along = |(time, _): &(f32, vec3)| {
+                let x = center.0[k] + motion.0[k] * time;
+                lo.0[k] < x && x < hi.0[k]
+            };
+            consider(hit.filter(along));
+        }
+    }
+    best
+}
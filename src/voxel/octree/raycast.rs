@@ -0,0 +1,154 @@
+use super::{child_offset, Octree};
+use crate::math::{ivec3, vec3, Vector};
+
+#[derive(Debug, Clone, Copy)]
+#[allow(unused)]
+pub struct RayHit {
+    pub point: vec3,
+    pub offset: [usize; 3],
+    pub normal: ivec3,
+    pub distance: f32,
+    pub voxel: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[allow(unused)]
+pub struct RayLeaf {
+    pub offset: [usize; 3],
+    pub extent: usize,
+    pub voxel: usize,
+    pub t_enter: f32,
+    pub t_exit: f32,
+    // Face through which the ray enters the leaf, zero if it starts inside
+    pub normal: ivec3,
+}
+
+#[derive(Debug, Clone)]
+pub struct RayLeaves<'a> {
+    tree: &'a Octree,
+    origin: vec3,
+    dir: vec3,
+    max_distance: f32,
+    // Child index bits to flip so that children are visited front to back
+    mirror: usize,
+    stack: Vec<(usize, [usize; 3], usize)>,
+}
+
+impl Octree {
+    /// Leaves pierced by the ray, front to back. Distances are measured in voxels along
+    /// the normalized `dir`, so `max_distance` may be `f32::INFINITY`.
+    #[allow(unused)]
+    pub fn ray_leaves(&self, origin: vec3, dir: vec3, max_distance: f32) -> RayLeaves<'_> {
+        let mut stack = Vec::new();
+        let length = dir.length();
+        let dir = if length > 0.0 {
+            stack.push((self.root, [0; 3], self.log_extent));
+            dir / length
+        } else {
+            dir
+        };
+        let mut mirror = 0;
+        for i in 0..3 {
+            if dir.0[i] < 0.0 {
+                mirror |= 1 << i;
+            }
+        }
+        RayLeaves {
+            tree: self,
+            origin,
+            dir,
+            max_distance,
+            mirror,
+            stack,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn raycast(&self, origin: vec3, dir: vec3, max_distance: f32) -> Option<RayHit> {
+        let leaf = self
+            .ray_leaves(origin, dir, max_distance)
+            .find(|leaf| leaf.voxel != !0)?;
+        let distance = leaf.t_enter.max(0.0);
+        let point = origin + dir.normalize() * distance;
+        let mut offset = [0; 3];
+        for (i, o) in offset.iter_mut().enumerate() {
+            let lo = leaf.offset[i] as f32;
+            let hi = (leaf.offset[i] + leaf.extent - 1) as f32;
+            *o = point.0[i].floor().clamp(lo, hi) as usize;
+        }
+        Some(RayHit {
+            point,
+            offset,
+            normal: leaf.normal,
+            distance,
+            voxel: leaf.voxel,
+        })
+    }
+}
+
+impl RayLeaves<'_> {
+    fn intersect(&self, offset: [usize; 3], extent: usize) -> Option<(f32, f32, ivec3)> {
+        let mut t_enter = f32::NEG_INFINITY;
+        let mut t_exit = f32::INFINITY;
+        let mut normal = Vector([0; 3]);
+        for (i, &oi) in offset.iter().enumerate() {
+            let lo = oi as f32;
+            let hi = (oi + extent) as f32;
+            let o = self.origin.0[i];
+            let d = self.dir.0[i];
+            if d == 0.0 {
+                if o < lo || o >= hi {
+                    return None;
+                }
+                continue;
+            }
+            let (near, far, sign) = if d > 0.0 { (lo, hi, -1) } else { (hi, lo, 1) };
+            let t_near = (near - o) / d;
+            let t_far = (far - o) / d;
+            if t_near > t_enter {
+                t_enter = t_near;
+                normal = Vector([0; 3]);
+                normal.0[i] = sign;
+            }
+            t_exit = t_exit.min(t_far);
+        }
+        if t_enter >= t_exit || t_exit <= 0.0 || t_enter > self.max_distance {
+            return None;
+        }
+        if t_enter < 0.0 {
+            normal = Vector([0; 3]);
+        }
+        Some((t_enter, t_exit, normal))
+    }
+}
+
+impl Iterator for RayLeaves<'_> {
+    type Item = RayLeaf;
+
+    fn next(&mut self) -> Option<RayLeaf> {
+        while let Some((i_node, offset, node_log_extent)) = self.stack.pop() {
+            let Some((t_enter, t_exit, normal)) = self.intersect(offset, 1 << node_log_extent)
+            else {
+                continue;
+            };
+            let node = &self.tree.nodes[i_node];
+            if node.is_leaf() {
+                return Some(RayLeaf {
+                    offset,
+                    extent: 1 << node_log_extent,
+                    voxel: node.voxel,
+                    t_enter,
+                    t_exit,
+                    normal,
+                });
+            }
+            for i in (0..8).rev() {
+                let i_child = i ^ self.mirror;
+                let next_offset = child_offset(offset, node_log_extent, i_child);
+                self.stack
+                    .push((node.children[i_child], next_offset, node_log_extent - 1));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,138 @@
+use super::VoxelInfo;
+use crate::{
+    layout::{assert_gpu_layout, gpu_layout, member_offset, GpuLayout, Std430},
+    math::{vec4, Vector},
+};
+use imgui::{TreeNodeFlags, Ui};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+gpu_layout! {
+    #[derive(Clone, Copy, Debug, Default)]
+    struct Material {
+        color: vec4,  // rgb --- diffuse colour, a --- opacity
+        params: vec4, // x --- emissive strength, y --- roughness
+    }
+}
+
+gpu_layout! {
+    // `materials` has as many entries as fit into the buffer
+    #[derive(Clone, Copy, Debug, Default)]
+    struct PaletteHeader {
+        material_count: u32,
+        materials: [Material; 0],
+    }
+}
+
+// As in `Material` of main.frag and octree.frag
+assert_gpu_layout!(
+    Std430,
+    Material {
+        color: 0,
+        params: 16,
+    },
+    32
+);
+
+// As in `PaletteSSBO` of main.frag and octree.frag
+assert_gpu_layout!(
+    Std430,
+    PaletteHeader {
+        material_count: 0,
+        materials: 16,
+    },
+    16
+);
+
+// Materials indexed by voxel id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Palette {
+    infos: Vec<VoxelInfo>,
+}
+
+impl Palette {
+    #[allow(unused)]
+    pub fn new(infos: Vec<VoxelInfo>) -> Self {
+        Self { infos }
+    }
+
+    #[allow(unused)]
+    pub fn infos(&self) -> &[VoxelInfo] {
+        &self.infos
+    }
+
+    #[allow(unused)]
+    pub fn get(&self, voxel: usize) -> Option<&VoxelInfo> {
+        self.infos.get(voxel)
+    }
+
+    pub fn try_save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let file = File::create(path)?;
+        let writer = BufWriter::new(file);
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+
+    pub fn try_load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    // Contents of `PaletteSSBO` with room for `max_count` materials
+    pub fn gpu_data(&self, max_count: usize) -> Vec<u8> {
+        let header_size = <PaletteHeader as GpuLayout<Std430>>::SIZE;
+        let materials_offset =
+            member_offset(<PaletteHeader as GpuLayout<Std430>>::MEMBERS, "materials");
+        let stride = <[Material; 1] as GpuLayout<Std430>>::SIZE;
+        let count = self.infos.len().min(max_count);
+        let mut out = vec![0; materials_offset + stride * max_count];
+        let header = PaletteHeader {
+            material_count: count as _,
+            materials: [],
+        };
+        GpuLayout::<Std430>::write(&header, &mut out[..header_size]);
+        for (i, info) in self.infos[..count].iter().enumerate() {
+            let [r, g, b] = info.diffuse_color;
+            let material = Material {
+                color: Vector([r, g, b, info.opacity]),
+                params: Vector([info.emissive, info.roughness, 0.0, 0.0]),
+            };
+            let out = &mut out[materials_offset + stride * i..][..stride];
+            GpuLayout::<Std430>::write(&material, out);
+        }
+        out
+    }
+
+    // Returns whether any material was changed
+    pub fn edit(&mut self, ui: &Ui) -> bool {
+        if !ui.collapsing_header("Palette", TreeNodeFlags::empty()) {
+            return false;
+        }
+        let mut changed = false;
+        for (voxel, info) in self.infos.iter_mut().enumerate() {
+            let _id = ui.push_id_usize(voxel);
+            ui.text(format!("Voxel {voxel}"));
+            ui.input_text("Name", &mut info.name).build();
+            changed |= ui.color_edit3("Colour", &mut info.diffuse_color);
+            changed |= ui.slider("Opacity", 0.0, 1.0, &mut info.opacity);
+            changed |= ui.slider("Emissive", 0.0, 16.0, &mut info.emissive);
+            changed |= ui.slider("Roughness", 0.0, 1.0, &mut info.roughness);
+            ui.separator();
+        }
+        if ui.button("Add material") {
+            self.infos.push(VoxelInfo::default());
+            changed = true;
+        }
+        ui.same_line();
+        if ui.button("Remove last") {
+            changed |= self.infos.pop().is_some();
+        }
+        changed
+    }
+}
@@ -0,0 +1,576 @@
+// MagicaVoxel .vox import and export.
+//
+// MagicaVoxel is Z-up, the octree is Y-up: a .vox voxel (x, y, z) lands at
+// octree (x, z, -y), shifted so that the lowest voxel is at the origin.
+// Voxel ids are palette indices minus one, so `palette[voxel]` is its colour.
+
+use super::{octree::Octree, VoxelInfo};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+const MAGIC: [u8; 4] = *b"VOX ";
+const VERSION: i32 = 200;
+const MAX_MODEL_EXTENT: usize = 256;
+const PALETTE_SIZE: usize = 255;
+
+#[derive(Debug)]
+#[allow(unused)]
+pub enum VoxError {
+    Io(io::Error),
+    Truncated,
+    BadMagic([u8; 4]),
+    Corrupt(&'static str),
+    UnsupportedVoxel(usize),
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, ".vox I/O error: {err}"),
+            Self::Truncated => write!(f, ".vox data is truncated"),
+            Self::BadMagic(magic) => write!(f, "not a .vox file (magic {magic:02x?})"),
+            Self::Corrupt(what) => write!(f, "corrupt .vox data: {what}"),
+            Self::UnsupportedVoxel(voxel) => {
+                write!(f, "voxel {voxel} does not fit in a .vox palette")
+            }
+        }
+    }
+}
+
+impl Error for VoxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for VoxError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::Io(err),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Model {
+    size: [i32; 3],
+    voxels: Vec<([u8; 3], u8)>,
+}
+
+// Rows of a signed permutation matrix, as MagicaVoxel stores rotations
+type Rotation = [[i32; 3]; 3];
+
+const IDENTITY: Rotation = [[1, 0, 0], [0, 1, 0], [0, 0, 1]];
+
+#[derive(Debug, Clone)]
+enum SceneNode {
+    Transform {
+        child: i32,
+        translation: [i32; 3],
+        rotation: Rotation,
+    },
+    Group(Vec<i32>),
+    Shape(Vec<i32>),
+}
+
+#[allow(unused)]
+pub fn load(path: impl AsRef<Path>) -> Result<(Octree, Vec<VoxelInfo>), VoxError> {
+    read(BufReader::new(File::open(path)?))
+}
+
+#[allow(unused)]
+pub fn save(path: impl AsRef<Path>, tree: &Octree, palette: &[VoxelInfo]) -> Result<(), VoxError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer, tree, palette)?;
+    Ok(writer.flush()?)
+}
+
+pub fn read(mut reader: impl Read) -> Result<(Octree, Vec<VoxelInfo>), VoxError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut cursor = Cursor(&data);
+    let magic = cursor.array()?;
+    if magic != MAGIC {
+        return Err(VoxError::BadMagic(magic));
+    }
+    let _version = cursor.i32()?;
+    let (id, mut main) = cursor.chunk()?;
+    if id != *b"MAIN" {
+        return Err(VoxError::Corrupt("first chunk is not MAIN"));
+    }
+
+    let mut models = Vec::new();
+    let mut size = None;
+    let mut scene = HashMap::new();
+    let mut palette = default_palette();
+    while !main.0.is_empty() {
+        let (id, mut content) = main.chunk()?;
+        match &id {
+            b"SIZE" => size = Some([content.i32()?, content.i32()?, content.i32()?]),
+            b"XYZI" => {
+                let size = size.take().ok_or(VoxError::Corrupt("XYZI without SIZE"))?;
+                let n_voxels = content.i32()?;
+                let mut voxels = Vec::new();
+                for _ in 0..n_voxels {
+                    let [x, y, z, i] = content.array()?;
+                    if i != 0 {
+                        voxels.push(([x, y, z], i));
+                    }
+                }
+                models.push(Model { size, voxels });
+            }
+            b"RGBA" => {
+                for info in &mut palette {
+                    let [r, g, b, a] = content.array()?;
+                    info.diffuse_color = [r, g, b].map(|c| c as f32 / 255.0);
+                    info.opacity = a as f32 / 255.0;
+                }
+            }
+            b"nTRN" => {
+                let id = content.i32()?;
+                content.dict()?;
+                let child = content.i32()?;
+                let _reserved = content.i32()?;
+                let _layer = content.i32()?;
+                let n_frames = content.i32()?;
+                let mut translation = [0; 3];
+                let mut rotation = IDENTITY;
+                // Animations are not supported, the first frame wins
+                for i_frame in 0..n_frames {
+                    let frame = content.dict()?;
+                    if i_frame == 0 {
+                        if let Some(t) = frame.get("_t") {
+                            translation = parse_translation(t)?;
+                        }
+                        if let Some(r) = frame.get("_r") {
+                            rotation = parse_rotation(r)?;
+                        }
+                    }
+                }
+                scene.insert(
+                    id,
+                    SceneNode::Transform {
+                        child,
+                        translation,
+                        rotation,
+                    },
+                );
+            }
+            b"nGRP" => {
+                let id = content.i32()?;
+                content.dict()?;
+                let n_children = content.i32()?;
+                let mut children = Vec::new();
+                for _ in 0..n_children {
+                    children.push(content.i32()?);
+                }
+                scene.insert(id, SceneNode::Group(children));
+            }
+            b"nSHP" => {
+                let id = content.i32()?;
+                content.dict()?;
+                let n_models = content.i32()?;
+                let mut shape_models = Vec::new();
+                for _ in 0..n_models {
+                    shape_models.push(content.i32()?);
+                    content.dict()?;
+                }
+                scene.insert(id, SceneNode::Shape(shape_models));
+            }
+            _ => {}
+        }
+    }
+
+    let mut placed = Vec::new();
+    if scene.is_empty() {
+        for model in &models {
+            for &([x, y, z], i) in &model.voxels {
+                placed.push(([x as i32, y as i32, z as i32], i));
+            }
+        }
+    } else {
+        place_node(&scene, &models, 0, [0; 3], IDENTITY, 0, &mut placed)?;
+    }
+
+    let mut lo = [i64::MAX; 3];
+    for &(pos, _) in &placed {
+        let pos = vox_to_tree(pos);
+        for i in 0..3 {
+            lo[i] = lo[i].min(pos[i]);
+        }
+    }
+    let mut tree = Octree::new();
+    for (pos, i) in placed {
+        let pos = vox_to_tree(pos);
+        let offset = [0, 1, 2].map(|i| (pos[i] - lo[i]) as usize);
+        tree.set(offset, [1; 3], i as usize - 1);
+    }
+    Ok((tree, palette))
+}
+
+pub fn write(mut writer: impl Write, tree: &Octree, palette: &[VoxelInfo]) -> Result<(), VoxError> {
+    let extent = tree.extent();
+    let mut chunks = HashMap::new();
+    for (offset, e) in tree.debug_boxes() {
+        let voxel = tree.get(offset);
+        if voxel >= PALETTE_SIZE {
+            return Err(VoxError::UnsupportedVoxel(voxel));
+        }
+        for z in offset[2]..offset[2] + e {
+            for y in offset[1]..offset[1] + e {
+                for x in offset[0]..offset[0] + e {
+                    let pos = tree_to_vox([x, y, z], extent);
+                    let chunk = pos.map(|c| c / MAX_MODEL_EXTENT);
+                    let local = pos.map(|c| (c % MAX_MODEL_EXTENT) as u8);
+                    chunks
+                        .entry(chunk)
+                        .or_insert_with(Vec::new)
+                        .push((local, voxel as u8 + 1));
+                }
+            }
+        }
+    }
+    let mut chunks: Vec<_> = chunks.into_iter().collect();
+    chunks.sort_by_key(|&([x, y, z], _)| (z, y, x));
+
+    let mut main = Vec::new();
+    for (_, voxels) in &chunks {
+        let size = [MAX_MODEL_EXTENT.min(extent) as i32; 3];
+        let mut content = Vec::new();
+        put_i32(&mut content, voxels.len() as i32);
+        for &([x, y, z], i) in voxels {
+            content.extend_from_slice(&[x, y, z, i]);
+        }
+        put_chunk(
+            &mut main,
+            b"SIZE",
+            &size.map(i32::to_le_bytes).concat(),
+            &[],
+        );
+        put_chunk(&mut main, b"XYZI", &content, &[]);
+    }
+
+    let mut root = Vec::new();
+    put_transform(&mut root, 0, 1, [0; 3]);
+    put_chunk(&mut main, b"nTRN", &root, &[]);
+    let mut group = Vec::new();
+    put_i32(&mut group, 1);
+    put_i32(&mut group, 0);
+    put_i32(&mut group, chunks.len() as i32);
+    for i_model in 0..chunks.len() {
+        put_i32(&mut group, 2 + 2 * i_model as i32);
+    }
+    put_chunk(&mut main, b"nGRP", &group, &[]);
+    for (i_model, (chunk, _)) in chunks.iter().enumerate() {
+        let id = 2 + 2 * i_model as i32;
+        let pivot = (MAX_MODEL_EXTENT.min(extent) / 2) as i32;
+        let translation = chunk.map(|c| (c * MAX_MODEL_EXTENT) as i32 + pivot);
+        let mut transform = Vec::new();
+        put_transform(&mut transform, id, id + 1, translation);
+        put_chunk(&mut main, b"nTRN", &transform, &[]);
+        let mut shape = Vec::new();
+        put_i32(&mut shape, id + 1);
+        put_i32(&mut shape, 0);
+        put_i32(&mut shape, 1);
+        put_i32(&mut shape, i_model as i32);
+        put_i32(&mut shape, 0);
+        put_chunk(&mut main, b"nSHP", &shape, &[]);
+    }
+
+    let mut rgba = Vec::new();
+    for i in 0..=PALETTE_SIZE {
+        let info = palette.get(i).cloned().unwrap_or_default();
+        let [r, g, b] = info.diffuse_color.map(to_unorm8);
+        rgba.extend_from_slice(&[r, g, b, to_unorm8(info.opacity)]);
+    }
+    put_chunk(&mut main, b"RGBA", &rgba, &[]);
+
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    let mut file = Vec::new();
+    put_chunk(&mut file, b"MAIN", &[], &main);
+    writer.write_all(&file)?;
+    Ok(())
+}
+
+fn place_node(
+    scene: &HashMap<i32, SceneNode>,
+    models: &[Model],
+    id: i32,
+    translation: [i32; 3],
+    rotation: Rotation,
+    depth: usize,
+    placed: &mut Vec<([i32; 3], u8)>,
+) -> Result<(), VoxError> {
+    if depth > scene.len() {
+        return Err(VoxError::Corrupt("cycle in scene graph"));
+    }
+    match scene.get(&id) {
+        None => Err(VoxError::Corrupt("missing scene node")),
+        Some(SceneNode::Transform {
+            child,
+            translation: t,
+            rotation: r,
+        }) => {
+            let t = rotate(rotation, *t);
+            let translation = [0, 1, 2].map(|i| translation[i] + t[i]);
+            let rotation = compose(rotation, *r);
+            place_node(
+                scene,
+                models,
+                *child,
+                translation,
+                rotation,
+                depth + 1,
+                placed,
+            )
+        }
+        Some(SceneNode::Group(children)) => {
+            for &child in children {
+                place_node(
+                    scene,
+                    models,
+                    child,
+                    translation,
+                    rotation,
+                    depth + 1,
+                    placed,
+                )?;
+            }
+            Ok(())
+        }
+        Some(SceneNode::Shape(shape_models)) => {
+            for &i_model in shape_models {
+                let model = usize::try_from(i_model)
+                    .ok()
+                    .and_then(|i| models.get(i))
+                    .ok_or(VoxError::Corrupt("shape refers to a missing model"))?;
+                let pivot = model.size.map(|s| s / 2);
+                for &([x, y, z], i) in &model.voxels {
+                    let local = [
+                        x as i32 - pivot[0],
+                        y as i32 - pivot[1],
+                        z as i32 - pivot[2],
+                    ];
+                    let p = rotate(rotation, local);
+                    placed.push(([0, 1, 2].map(|i| translation[i] + p[i]), i));
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn rotate(rotation: Rotation, v: [i32; 3]) -> [i32; 3] {
+    rotation.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+fn compose(a: Rotation, b: Rotation) -> Rotation {
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn parse_translation(value: &str) -> Result<[i32; 3], VoxError> {
+    let mut out = [0; 3];
+    let mut parts = value.split_whitespace();
+    for x in &mut out {
+        *x = parts
+            .next()
+            .and_then(|part| part.parse().ok())
+            .ok_or(VoxError::Corrupt("bad translation"))?;
+    }
+    Ok(out)
+}
+
+fn parse_rotation(value: &str) -> Result<Rotation, VoxError> {
+    let bits: u8 = value
+        .trim()
+        .parse()
+        .map_err(|_| VoxError::Corrupt("bad rotation"))?;
+    let i0 = (bits & 3) as usize;
+    let i1 = ((bits >> 2) & 3) as usize;
+    if i0 > 2 || i1 > 2 || i0 == i1 {
+        return Err(VoxError::Corrupt("bad rotation"));
+    }
+    let i2 = 3 - i0 - i1;
+    let mut out = [[0; 3]; 3];
+    for (row, i) in [i0, i1, i2].into_iter().enumerate() {
+        out[row][i] = if bits & (0x10 << row) != 0 { -1 } else { 1 };
+    }
+    Ok(out)
+}
+
+fn vox_to_tree([x, y, z]: [i32; 3]) -> [i64; 3] {
+    [x as i64, z as i64, -(y as i64)]
+}
+
+fn tree_to_vox([x, y, z]: [usize; 3], extent: usize) -> [usize; 3] {
+    [x, extent - 1 - z, y]
+}
+
+fn to_unorm8(x: f32) -> u8 {
+    (x.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Palette MagicaVoxel uses for files without an RGBA chunk: a 6x6x6 colour
+// cube followed by blue, green, red and grey ramps
+fn default_palette() -> Vec<VoxelInfo> {
+    const CUBE: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let mut rgb = Vec::new();
+    for r in CUBE {
+        for g in CUBE {
+            for b in CUBE {
+                rgb.push([r, g, b]);
+            }
+        }
+    }
+    rgb.pop();
+    for i in [2, 1, 0] {
+        for c in RAMP {
+            let mut color = [0; 3];
+            color[i] = c;
+            rgb.push(color);
+        }
+    }
+    for c in RAMP {
+        rgb.push([c; 3]);
+    }
+    rgb.into_iter()
+        .map(|color| VoxelInfo {
+            diffuse_color: color.map(|c| c as f32 / 255.0),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn put_i32(out: &mut Vec<u8>, x: i32) {
+    out.extend_from_slice(&x.to_le_bytes());
+}
+
+fn put_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    out.extend_from_slice(id);
+    put_i32(out, content.len() as i32);
+    put_i32(out, children.len() as i32);
+    out.extend_from_slice(content);
+    out.extend_from_slice(children);
+}
+
+fn put_transform(out: &mut Vec<u8>, id: i32, child: i32, translation: [i32; 3]) {
+    put_i32(out, id);
+    put_i32(out, 0);
+    put_i32(out, child);
+    put_i32(out, -1);
+    put_i32(out, -1);
+    put_i32(out, 1);
+    if translation == [0; 3] {
+        put_i32(out, 0);
+    } else {
+        let value = format!("{} {} {}", translation[0], translation[1], translation[2]);
+        put_i32(out, 1);
+        put_i32(out, 2);
+        out.extend_from_slice(b"_t");
+        put_i32(out, value.len() as i32);
+        out.extend_from_slice(value.as_bytes());
+    }
+}
+
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], VoxError> {
+        if n > self.0.len() {
+            return Err(VoxError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], VoxError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn i32(&mut self) -> Result<i32, VoxError> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn len(&mut self) -> Result<usize, VoxError> {
+        usize::try_from(self.i32()?).map_err(|_| VoxError::Corrupt("negative length"))
+    }
+
+    // MAIN keeps everything in its children, other chunks we read have no children
+    fn chunk(&mut self) -> Result<([u8; 4], Cursor<'a>), VoxError> {
+        let id = self.array()?;
+        let content_size = self.len()?;
+        let children_size = self.len()?;
+        let content = self.bytes(content_size)?;
+        let children = self.bytes(children_size)?;
+        if id == *b"MAIN" {
+            return Ok((id, Cursor(children)));
+        }
+        Ok((id, Cursor(content)))
+    }
+
+    fn string(&mut self) -> Result<String, VoxError> {
+        let len = self.len()?;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| VoxError::Corrupt("non-UTF-8 string"))
+    }
+
+    fn dict(&mut self) -> Result<HashMap<String, String>, VoxError> {
+        let n_pairs = self.i32()?;
+        let mut out = HashMap::new();
+        for _ in 0..n_pairs {
+            let key = self.string()?;
+            let value = self.string()?;
+            out.insert(key, value);
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut tree = Octree::with_log_extent(3);
+        tree.set([0, 0, 0], [8, 1, 8], 0);
+        tree.set([1, 2, 3], [3, 1, 2], 5);
+        tree.set([7, 7, 7], [1, 1, 1], 254);
+        let mut palette = default_palette();
+        palette[5].diffuse_color = [12.0 / 255.0, 34.0 / 255.0, 56.0 / 255.0];
+        palette[5].opacity = 128.0 / 255.0;
+
+        let mut data = Vec::new();
+        write(&mut data, &tree, &palette).unwrap();
+        let (read_tree, read_palette) = read(&data[..]).unwrap();
+        assert!(read_tree.structurally_eq(&tree));
+        assert_eq!(read_palette.len(), palette.len());
+        for (a, b) in read_palette.iter().zip(&palette) {
+            assert_eq!(a.diffuse_color, b.diffuse_color);
+            assert_eq!(a.opacity, b.opacity);
+        }
+    }
+
+    #[test]
+    fn unsupported_voxel() {
+        let mut tree = Octree::with_log_extent(1);
+        tree.set([0, 0, 0], [1, 1, 1], PALETTE_SIZE);
+        assert!(matches!(
+            write(&mut Vec::new(), &tree, &default_palette()),
+            Err(VoxError::UnsupportedVoxel(PALETTE_SIZE))
+        ));
+    }
+}
@@ -0,0 +1,496 @@
+// Conversion of triangle meshes into voxels.
+//
+// The mesh is scaled uniformly so that its bounding box fits the tree, lowest
+// corner at the origin, axes are kept as they are. A voxel belongs to the
+// surface if any triangle touches its closed box, so thin and sloped parts
+// never fall apart into disconnected voxels.
+
+use super::{octree::Octree, VoxelInfo};
+use crate::math::{vec3, Vector};
+use image::RgbaImage;
+use std::{collections::HashMap, error::Error, fmt, path::Path};
+
+#[derive(Debug)]
+#[allow(unused)]
+pub enum VoxelizeError {
+    Obj(tobj::LoadError),
+    Image(image::ImageError),
+}
+
+impl fmt::Display for VoxelizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Obj(err) => write!(f, "failed to load .obj: {err}"),
+            Self::Image(err) => write!(f, "failed to load texture: {err}"),
+        }
+    }
+}
+
+impl Error for VoxelizeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Obj(err) => Some(err),
+            Self::Image(err) => Some(err),
+        }
+    }
+}
+
+impl From<tobj::LoadError> for VoxelizeError {
+    fn from(err: tobj::LoadError) -> Self {
+        Self::Obj(err)
+    }
+}
+
+impl From<image::ImageError> for VoxelizeError {
+    fn from(err: image::ImageError) -> Self {
+        Self::Image(err)
+    }
+}
+
+// Where voxel ids come from
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoxelSource {
+    // Index of the face's material, faces without one get an extra default entry
+    Material,
+    // Texture colour at the point of the face closest to the voxel centre,
+    // quantized to 5 bits per channel. The texture is the diffuse map of the
+    // face's material or else the PNG next to the .obj with the same name,
+    // faces without either get the material colour.
+    Texture,
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fill {
+    // Only voxels touched by the surface
+    Surface,
+    // Voxels with centres between an odd and an even crossing of the mesh
+    // along X, which needs a watertight mesh
+    Parity,
+    // Voxels not reachable from the tree boundary without crossing the surface,
+    // which tolerates small holes up to a voxel but needs a byte per voxel
+    Flood,
+}
+
+// Loads the .obj and voxelizes it into a tree of extent `1 << log_extent`,
+// returning the tree with the palette its voxel ids index
+#[allow(unused)]
+pub fn load_obj(
+    path: impl AsRef<Path>,
+    log_extent: usize,
+    source: VoxelSource,
+    fill: Fill,
+) -> Result<(Octree, Vec<VoxelInfo>), VoxelizeError> {
+    let path = path.as_ref();
+    let (models, materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+    // A missing .mtl is not fatal, faces just lose their materials
+    let materials = materials.unwrap_or_default();
+
+    let mut triangles = Vec::new();
+    // Model and first index of each triangle
+    let mut faces = Vec::new();
+    for (i_model, model) in models.iter().enumerate() {
+        let mesh = &model.mesh;
+        let position = |i: u32| {
+            let i = 3 * i as usize;
+            Vector([
+                mesh.positions[i],
+                mesh.positions[i + 1],
+                mesh.positions[i + 2],
+            ])
+        };
+        for i in (0..mesh.indices.len() / 3).map(|i| 3 * i) {
+            triangles.push([0, 1, 2].map(|k| position(mesh.indices[i + k])));
+            faces.push((i_model, i));
+        }
+    }
+
+    let mut palette: Vec<VoxelInfo> = materials
+        .iter()
+        .map(|material| VoxelInfo {
+            name: material.name.clone(),
+            diffuse_color: material.diffuse.unwrap_or([1.0; 3]),
+            opacity: material.dissolve.unwrap_or(1.0),
+            ..Default::default()
+        })
+        .collect();
+    // Faces referring to materials which failed to load have none
+    let material_id = |i_model: usize| {
+        models[i_model]
+            .mesh
+            .material_id
+            .filter(|&i_material| i_material < materials.len())
+    };
+    let tree = match source {
+        VoxelSource::Material => {
+            let default_voxel = palette.len();
+            let material_of = |i_triangle: usize| {
+                let (i_model, _) = faces[i_triangle];
+                material_id(i_model).unwrap_or(default_voxel)
+            };
+            if (0..models.len()).any(|i_model| material_id(i_model).is_none()) {
+                palette.push(VoxelInfo {
+                    name: "default".into(),
+                    ..Default::default()
+                });
+            }
+            voxelize(&triangles, log_extent, fill, |i_triangle, _| {
+                material_of(i_triangle)
+            })
+        }
+        VoxelSource::Texture => {
+            let mut textures = Vec::with_capacity(materials.len());
+            for material in &materials {
+                let texture = match &material.diffuse_texture {
+                    Some(name) => {
+                        let texture_path = path.parent().unwrap_or(Path::new("")).join(name);
+                        Some(image::open(texture_path)?.to_rgba8())
+                    }
+                    None => None,
+                };
+                textures.push(texture);
+            }
+            let matching_path = path.with_extension("png");
+            let matching = if matching_path.exists() {
+                Some(image::open(matching_path)?.to_rgba8())
+            } else {
+                None
+            };
+
+            let mut colors = HashMap::new();
+            let mut color_palette = Vec::new();
+            let tree = voxelize(&triangles, log_extent, fill, |i_triangle, weights| {
+                let (i_model, i_first) = faces[i_triangle];
+                let mesh = &models[i_model].mesh;
+                let texture = match material_id(i_model) {
+                    Some(i_material) => textures[i_material].as_ref().or(matching.as_ref()),
+                    None => matching.as_ref(),
+                };
+                let color = match texture {
+                    Some(texture) if !mesh.texcoords.is_empty() => {
+                        let mut uv = [0.0; 2];
+                        for (k, w) in weights.0.into_iter().enumerate() {
+                            let j = 2 * mesh.indices[i_first + k] as usize;
+                            uv[0] += w * mesh.texcoords[j];
+                            uv[1] += w * mesh.texcoords[j + 1];
+                        }
+                        sample(texture, uv)
+                    }
+                    _ => match material_id(i_model) {
+                        Some(i_material) => palette[i_material].diffuse_color,
+                        None => [1.0; 3],
+                    },
+                };
+                let key = color.map(|c| (c.clamp(0.0, 1.0) * 31.0).round() as u8);
+                *colors.entry(key).or_insert_with(|| {
+                    color_palette.push(VoxelInfo {
+                        diffuse_color: key.map(|c| c as f32 / 31.0),
+                        ..Default::default()
+                    });
+                    color_palette.len() - 1
+                })
+            });
+            palette = color_palette;
+            tree
+        }
+    };
+    Ok((tree, palette))
+}
+
+// Voxelizes triangles in any units, `voxel` gets the triangle index and the
+// barycentric coordinates of the point of it the voxel is coloured by
+#[allow(unused)]
+pub fn voxelize(
+    triangles: &[[vec3; 3]],
+    log_extent: usize,
+    fill: Fill,
+    mut voxel: impl FnMut(usize, vec3) -> usize,
+) -> Octree {
+    let mut tree = Octree::with_log_extent(log_extent);
+    let extent = tree.extent();
+    let Some(triangles) = fit(triangles, extent) else {
+        return tree;
+    };
+    let to_voxel = |x: f32| (x.max(0.0) as usize).min(extent - 1);
+
+    // Surface voxels with the squared distance from their centre to the
+    // triangle they take the id from, the closest triangle wins
+    let mut surface: HashMap<[usize; 3], (f32, usize, vec3)> = HashMap::new();
+    for (i_triangle, triangle) in triangles.iter().enumerate() {
+        // Vertices on a voxel boundary touch the voxels on both sides
+        let (lo, hi) = triangle_bounds(triangle);
+        let lo = lo.0.map(|x| to_voxel(x.ceil() - 1.0));
+        let hi = hi.0.map(to_voxel);
+        for z in lo[2]..=hi[2] {
+            for y in lo[1]..=hi[1] {
+                for x in lo[0]..=hi[0] {
+                    let center = Vector([x, y, z].map(|c| c as f32 + 0.5));
+                    if !triangle_touches_box(triangle, center) {
+                        continue;
+                    }
+                    let weights = closest_point_weights(triangle, center);
+                    let closest = triangle[0] * weights.0[0]
+                        + triangle[1] * weights.0[1]
+                        + triangle[2] * weights.0[2];
+                    let d = closest - center;
+                    let distance = d.dot(d);
+                    match surface.get(&[x, y, z]) {
+                        Some(&(best, _, _)) if best <= distance => {}
+                        _ => {
+                            surface.insert([x, y, z], (distance, i_triangle, weights));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let surface: HashMap<_, _> = surface
+        .into_iter()
+        .map(|(offset, (_, i_triangle, weights))| (offset, voxel(i_triangle, weights)))
+        .collect();
+    match fill {
+        Fill::Surface => {}
+        Fill::Parity => fill_parity(&mut tree, &triangles, &mut voxel),
+        Fill::Flood => fill_flood(&mut tree, &surface),
+    }
+    for (offset, voxel) in surface {
+        tree.set(offset, [1; 3], voxel);
+    }
+    tree
+}
+
+// Triangles moved and scaled into `[0, extent]^3`
+fn fit(triangles: &[[vec3; 3]], extent: usize) -> Option<Vec<[vec3; 3]>> {
+    let (lo, hi) = triangles
+        .iter()
+        .map(triangle_bounds)
+        .reduce(|(lo_a, hi_a), (lo_b, hi_b)| (min(lo_a, lo_b), max(hi_a, hi_b)))?;
+    let size = (0..3).map(|i| hi.0[i] - lo.0[i]).fold(0.0, f32::max);
+    let scale = if size > 0.0 {
+        extent as f32 / size
+    } else {
+        1.0
+    };
+    Some(
+        triangles
+            .iter()
+            .map(|triangle| triangle.map(|v| (v - lo) * scale))
+            .collect(),
+    )
+}
+
+fn fill_parity(
+    tree: &mut Octree,
+    triangles: &[[vec3; 3]],
+    voxel: &mut impl FnMut(usize, vec3) -> usize,
+) {
+    let extent = tree.extent();
+    // Rays along X through voxel centres, slightly off so that they never
+    // pass exactly through shared edges and vertices to be counted twice
+    const JITTER: [f32; 2] = [1.234_567e-4, 2.345_678e-4];
+    let mut crossings = vec![Vec::new(); extent * extent];
+    for (i_triangle, triangle) in triangles.iter().enumerate() {
+        let (lo, hi) = triangle_bounds(triangle);
+        let first = [lo.0[1], lo.0[2]].map(|x| (x - 0.5).ceil().max(0.0) as usize);
+        let last =
+            [hi.0[1], hi.0[2]].map(|x| ((x - 0.5).floor() as isize).min(extent as isize - 1));
+        if last[0] < 0 || last[1] < 0 {
+            continue;
+        }
+        for z in first[1]..=last[1] as usize {
+            for y in first[0]..=last[0] as usize {
+                let p = [y as f32 + 0.5 + JITTER[0], z as f32 + 0.5 + JITTER[1]];
+                let Some(weights) = project_yz(triangle, p) else {
+                    continue;
+                };
+                let x = (0..3).map(|k| weights.0[k] * triangle[k].0[0]).sum::<f32>();
+                crossings[y + extent * z].push((x, i_triangle, weights));
+            }
+        }
+    }
+    for (i_row, row) in crossings.iter_mut().enumerate() {
+        row.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (y, z) = (i_row % extent, i_row / extent);
+        for pair in row.chunks_exact(2) {
+            let (x_enter, i_triangle, weights) = pair[0];
+            let first = (x_enter - 0.5).ceil().max(0.0) as usize;
+            let last = ((pair[1].0 - 0.5).floor() + 1.0).clamp(0.0, extent as f32) as usize;
+            if first < last {
+                tree.set(
+                    [first, y, z],
+                    [last - first, 1, 1],
+                    voxel(i_triangle, weights),
+                );
+            }
+        }
+    }
+}
+
+fn fill_flood(tree: &mut Octree, surface: &HashMap<[usize; 3], usize>) {
+    const UNKNOWN: u8 = 0;
+    const SURFACE: u8 = 1;
+    const OUTSIDE: u8 = 2;
+    let extent = tree.extent();
+    let index = |[x, y, z]: [usize; 3]| x + extent * (y + extent * z);
+    let mut state = vec![UNKNOWN; extent * extent * extent];
+    for offset in surface.keys() {
+        state[index(*offset)] = SURFACE;
+    }
+    let mut stack = Vec::new();
+    for z in 0..extent {
+        for y in 0..extent {
+            for x in 0..extent {
+                let p = [x, y, z];
+                let on_boundary = p.iter().any(|&c| c == 0 || c == extent - 1);
+                if on_boundary && state[index(p)] == UNKNOWN {
+                    state[index(p)] = OUTSIDE;
+                    stack.push(p);
+                }
+            }
+        }
+    }
+    while let Some(p) = stack.pop() {
+        for axis in 0..3 {
+            for next in [p[axis].wrapping_sub(1), p[axis] + 1] {
+                if next >= extent {
+                    continue;
+                }
+                let mut q = p;
+                q[axis] = next;
+                if state[index(q)] == UNKNOWN {
+                    state[index(q)] = OUTSIDE;
+                    stack.push(q);
+                }
+            }
+        }
+    }
+    // Enclosed runs take the voxel of the surface right before them
+    for z in 0..extent {
+        for y in 0..extent {
+            let mut current = 0;
+            let mut x = 0;
+            while x < extent {
+                match state[index([x, y, z])] {
+                    SURFACE => current = surface[&[x, y, z]],
+                    UNKNOWN => {
+                        let first = x;
+                        while x + 1 < extent && state[index([x + 1, y, z])] == UNKNOWN {
+                            x += 1;
+                        }
+                        tree.set([first, y, z], [x + 1 - first, 1, 1], current);
+                    }
+                    _ => {}
+                }
+                x += 1;
+            }
+        }
+    }
+}
+
+fn triangle_bounds([a, b, c]: &[vec3; 3]) -> (vec3, vec3) {
+    (min(min(*a, *b), *c), max(max(*a, *b), *c))
+}
+
+fn min(a: vec3, b: vec3) -> vec3 {
+    Vector([0, 1, 2].map(|i| a.0[i].min(b.0[i])))
+}
+
+fn max(a: vec3, b: vec3) -> vec3 {
+    Vector([0, 1, 2].map(|i| a.0[i].max(b.0[i])))
+}
+
+fn sample(texture: &RgbaImage, [u, v]: [f32; 2]) -> [f32; 3] {
+    let (width, height) = texture.dimensions();
+    let x = (u.rem_euclid(1.0) * width as f32) as u32;
+    // Texture coordinates start at the bottom row
+    let y = ((1.0 - v).rem_euclid(1.0) * height as f32) as u32;
+    let pixel = texture.get_pixel(x.min(width - 1), y.min(height - 1));
+    [0, 1, 2].map(|i| pixel.0[i] as f32 / 255.0)
+}
+
+// Separating axis test of the triangle against the closed unit voxel box
+fn triangle_touches_box(triangle: &[vec3; 3], center: vec3) -> bool {
+    const HALF: f32 = 0.5;
+    let v = triangle.map(|p| p - center);
+    let separated = |axis: vec3| {
+        let r = HALF * (axis.0[0].abs() + axis.0[1].abs() + axis.0[2].abs());
+        let p = v.map(|p| p.dot(axis));
+        p[0].min(p[1]).min(p[2]) > r || p[0].max(p[1]).max(p[2]) < -r
+    };
+    let unit = |i: usize| {
+        let mut axis = Vector([0.0; 3]);
+        axis.0[i] = 1.0;
+        axis
+    };
+    let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+    if (0..3).any(|i| separated(unit(i))) || separated(edges[0].cross(edges[1])) {
+        return false;
+    }
+    !edges
+        .iter()
+        .any(|&edge| (0..3).any(|i| separated(edge.cross(unit(i)))))
+}
+
+// Barycentric coordinates of the point of the triangle closest to `p`
+fn closest_point_weights([a, b, c]: &[vec3; 3], p: vec3) -> vec3 {
+    let ab = *b - *a;
+    let ac = *c - *a;
+    let ap = p - *a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return Vector([1.0, 0.0, 0.0]);
+    }
+    let bp = p - *b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return Vector([0.0, 1.0, 0.0]);
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let t = d1 / (d1 - d3);
+        return Vector([1.0 - t, t, 0.0]);
+    }
+    let cp = p - *c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return Vector([0.0, 0.0, 1.0]);
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let t = d2 / (d2 - d6);
+        return Vector([1.0 - t, 0.0, t]);
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let t = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return Vector([0.0, 1.0 - t, t]);
+    }
+    let denominator = va + vb + vc;
+    if denominator == 0.0 {
+        return Vector([1.0, 0.0, 0.0]);
+    }
+    let v = vb / denominator;
+    let w = vc / denominator;
+    Vector([1.0 - v - w, v, w])
+}
+
+// Barycentric coordinates of `p` in the projection of the triangle onto YZ
+fn project_yz(triangle: &[vec3; 3], p: [f32; 2]) -> Option<vec3> {
+    let [a, b, c] = triangle.map(|v| [v.0[1], v.0[2]]);
+    let edge =
+        |u: [f32; 2], v: [f32; 2]| (v[0] - u[0]) * (p[1] - u[1]) - (v[1] - u[1]) * (p[0] - u[0]);
+    let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+    if area == 0.0 {
+        return None;
+    }
+    let weights = [edge(b, c), edge(c, a), edge(a, b)].map(|w| w / area);
+    if weights.iter().any(|&w| w < 0.0) {
+        return None;
+    }
+    Some(Vector(weights))
+}
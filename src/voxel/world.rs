@@ -0,0 +1,168 @@
+use super::octree::{BrushMode, Octree, Shape};
+use crate::math::Vector;
+use std::collections::{HashMap, HashSet};
+
+// Unbounded voxel world split into cubic `Octree` chunks of the same size.
+// Chunk `[cx, cy, cz]` covers voxels from `[cx, cy, cz] * chunk_extent()` on.
+#[derive(Debug, Clone)]
+pub struct World {
+    log_chunk_extent: usize,
+    chunks: HashMap<[i32; 3], Octree>,
+    // Chunks changed since the last `take_dirty`, including removed ones
+    dirty: HashSet<[i32; 3]>,
+}
+
+impl World {
+    #[allow(unused)]
+    pub fn new(log_chunk_extent: usize) -> Self {
+        Self {
+            log_chunk_extent,
+            chunks: HashMap::new(),
+            dirty: HashSet::new(),
+        }
+    }
+
+    pub fn chunk_extent(&self) -> i64 {
+        1 << self.log_chunk_extent
+    }
+
+    #[allow(unused)]
+    pub fn chunk(&self, coord: [i32; 3]) -> Option<&Octree> {
+        self.chunks.get(&coord)
+    }
+
+    #[allow(unused)]
+    pub fn chunks(&self) -> impl Iterator<Item = ([i32; 3], &Octree)> {
+        self.chunks.iter().map(|(&coord, tree)| (coord, tree))
+    }
+
+    // Coordinates of the chunks to be remeshed or uploaded again, clearing the flags
+    #[allow(unused)]
+    pub fn take_dirty(&mut self) -> Vec<[i32; 3]> {
+        self.dirty.drain().collect()
+    }
+
+    #[allow(unused)]
+    pub fn get(&self, p: [i64; 3]) -> usize {
+        let (coord, local) = self.split(p);
+        match self.chunks.get(&coord) {
+            Some(tree) => tree.get(local),
+            None => !0,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn set(&mut self, offset: [i64; 3], extent: [usize; 3], voxel: usize) {
+        if extent.contains(&0) {
+            return;
+        }
+        let e = self.chunk_extent();
+        let end = [0, 1, 2].map(|i| offset[i] + extent[i] as i64);
+        let lo = self.split(offset).0;
+        let hi = self.split(end.map(|x| x - 1)).0;
+        for coord in chunk_range(lo, hi) {
+            let origin = coord.map(|c| c as i64 * e);
+            let local_offset = [0, 1, 2].map(|i| (offset[i] - origin[i]).max(0));
+            let local_end = [0, 1, 2].map(|i| (end[i] - origin[i]).min(e));
+            let local_extent = [0, 1, 2].map(|i| (local_end[i] - local_offset[i]) as usize);
+            self.edit_chunk(coord, voxel == !0, |tree| {
+                tree.set(local_offset.map(|x| x as usize), local_extent, voxel);
+            });
+        }
+    }
+
+    #[allow(unused)]
+    pub fn apply_brush(&mut self, shape: &Shape, mode: BrushMode, voxel: usize) {
+        let e = self.chunk_extent();
+        // Only a bounded union can fill chunks which do not exist yet
+        let coords: Vec<_> = match (mode, shape.bounds()) {
+            (BrushMode::Union, Some((lo, hi))) => {
+                let lo = self.split(lo.map(|x| (x - 0.5).ceil() as i64)).0;
+                let hi = self.split(hi.map(|x| (x - 0.5).floor() as i64)).0;
+                chunk_range(lo, hi).collect()
+            }
+            (BrushMode::Intersect, _) | (_, None) => self.chunks.keys().copied().collect(),
+            (_, Some((lo, hi))) => {
+                let lo = self.split(lo.map(|x| (x - 0.5).ceil() as i64)).0;
+                let hi = self.split(hi.map(|x| (x - 0.5).floor() as i64)).0;
+                let within = |c: &[i32; 3]| (0..3).all(|i| lo[i] <= c[i] && c[i] <= hi[i]);
+                self.chunks.keys().copied().filter(within).collect()
+            }
+        };
+        for coord in coords {
+            let origin = Vector(coord.map(|c| (c as i64 * e) as f32));
+            let local_shape = shape.translated(-origin);
+            self.edit_chunk(coord, mode != BrushMode::Union, |tree| {
+                tree.apply_brush_clipped(&local_shape, mode, voxel);
+            });
+        }
+    }
+
+    // Chunk coordinates and position within the chunk
+    fn split(&self, p: [i64; 3]) -> ([i32; 3], [usize; 3]) {
+        let e = self.chunk_extent();
+        (
+            p.map(|x| x.div_euclid(e) as i32),
+            p.map(|x| x.rem_euclid(e) as usize),
+        )
+    }
+
+    // Missing chunks are created unless `existing_only`, and dropped again if left empty.
+    // Chunks are marked dirty only if the edit changed any of their voxels.
+    fn edit_chunk(&mut self, coord: [i32; 3], existing_only: bool, f: impl FnOnce(&mut Octree)) {
+        let existed = self.chunks.contains_key(&coord);
+        if existing_only && !existed {
+            return;
+        }
+        let log_chunk_extent = self.log_chunk_extent;
+        let tree = self
+            .chunks
+            .entry(coord)
+            .or_insert_with(|| Octree::with_log_extent(log_chunk_extent));
+        let hash = tree.content_hash();
+        f(tree);
+        let changed = tree.content_hash() != hash;
+        if tree.is_empty() {
+            self.chunks.remove(&coord);
+        }
+        if changed {
+            self.dirty.insert(coord);
+        }
+    }
+}
+
+fn chunk_range(lo: [i32; 3], hi: [i32; 3]) -> impl Iterator<Item = [i32; 3]> {
+    (lo[2]..=hi[2]).flat_map(move |z| {
+        (lo[1]..=hi[1]).flat_map(move |y| (lo[0]..=hi[0]).map(move |x| [x, y, z]))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changes_are_dirty() {
+        let mut world = World::new(3);
+        world.set([-4, 0, 0], [8, 2, 2], 1);
+        let mut dirty = world.take_dirty();
+        dirty.sort();
+        assert_eq!(dirty, [[-1, 0, 0], [0, 0, 0]]);
+
+        world.set([-4, 0, 0], [8, 2, 2], 1);
+        world.set([20, 20, 20], [3, 3, 3], !0);
+        let sphere = Shape::Sphere {
+            center: Vector([4.0, 6.0, 4.0]),
+            radius: 1.5,
+        };
+        world.apply_brush(&sphere, BrushMode::Subtract, 0);
+        assert!(world.take_dirty().is_empty());
+        assert_eq!(world.chunks().count(), 2);
+
+        world.set([1, 1, 1], [1, 1, 1], 2);
+        assert_eq!(world.take_dirty(), [[0, 0, 0]]);
+        world.set([-4, 0, 0], [4, 2, 2], !0);
+        assert_eq!(world.take_dirty(), [[-1, 0, 0]]);
+        assert!(world.chunk([-1, 0, 0]).is_none());
+    }
+}
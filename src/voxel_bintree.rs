@@ -6,7 +6,7 @@ pub enum Node {
 
 use Node::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VoxelBintree {
     nodes: Vec<Option<Node>>,
     free_nodes: Vec<usize>,
@@ -28,10 +28,7 @@ impl Node {
     }
 
     fn is_branch(&self) -> bool {
-        match self {
-            Leaf(_) => true,
-            Branch(_) => false,
-        }
+        !self.is_leaf()
     }
 
     fn unwrap_value(&self) -> usize {
@@ -58,24 +55,68 @@ impl VoxelBintree {
         }
     }
 
+    // Values past the end of the slice up to the next power of two are 0
+    pub fn from_slice(values: &[usize]) -> Self {
+        let mut log_size = 0;
+        while 1 << log_size < values.len() {
+            log_size += 1;
+        }
+        let mut self_ = Self {
+            nodes: vec![None],
+            free_nodes: Vec::new(),
+            log_size,
+        };
+        self_.nodes[0] = Some(self_.slice_descend(values, 1 << log_size));
+        self_
+    }
+
+    fn slice_descend(&mut self, values: &[usize], size: usize) -> Node {
+        if values.is_empty() {
+            return Leaf(0);
+        }
+        if size == 1 {
+            return Leaf(values[0]);
+        }
+        let half_size = size / 2;
+        let (left, right) = values.split_at(half_size.min(values.len()));
+        let children = [
+            self.slice_descend(left, half_size),
+            self.slice_descend(right, half_size),
+        ];
+        if let [Leaf(x), Leaf(y)] = children {
+            if x == y {
+                return Leaf(x);
+            }
+        }
+        Branch(children.map(|child| {
+            self.nodes.push(Some(child));
+            self.nodes.len() - 1
+        }))
+    }
+
+    pub fn size(&self) -> usize {
+        1 << self.log_size
+    }
+
     pub fn get(&self, idx: usize) -> usize {
         if idx >= 1 << self.log_size {
             return 0;
         }
-        let (node_idx, _, _) = self.descend(idx);
-        self.nodes[node_idx].unwrap().unwrap_value()
+        let mut node_idx = 0;
+        let mut node_log_size = self.log_size;
+        loop {
+            match self.nodes[node_idx].unwrap() {
+                Leaf(x) => return x,
+                Branch(children) => {
+                    node_log_size -= 1;
+                    node_idx = children[(idx >> node_log_size) & 1];
+                }
+            }
+        }
     }
 
     pub fn set(&mut self, idx: usize, x: usize) {
-        while idx >= 1 << self.log_size {
-            let new_left_idx = self.alloc_node();
-            let new_right_idx = self.alloc_node();
-            self.nodes[new_left_idx] = Some(Leaf(0));
-            self.nodes[new_right_idx] = Some(Leaf(0));
-            self.nodes.swap(0, new_left_idx);
-            self.nodes[0] = Some(Branch([new_left_idx, new_right_idx]));
-            self.log_size += 1;
-        }
+        self.grow(idx + 1);
         let (mut node_idx, mut node_log_size, mut stack) = self.descend(idx);
         let node = &self.nodes[node_idx];
         let old_value = node.unwrap().unwrap_value();
@@ -107,6 +148,98 @@ impl VoxelBintree {
         }
     }
 
+    pub fn set_range(&mut self, start: usize, len: usize, x: usize) {
+        if len == 0 {
+            return;
+        }
+        self.grow(start + len);
+        self.set_range_descend(0, 0, self.log_size, start, start + len, x);
+    }
+
+    // Maximal runs of equal values as `(start, len, value)`, covering the whole size
+    pub fn runs(&self) -> Runs<'_> {
+        Runs {
+            tree: self,
+            stack: vec![(0, 0, self.log_size)],
+            pending: None,
+        }
+    }
+
+    fn grow(&mut self, size: usize) {
+        while size > 1 << self.log_size {
+            // Values past the end are 0 already
+            if self.nodes[0] == Some(Leaf(0)) {
+                self.log_size += 1;
+                continue;
+            }
+            let new_left_idx = self.alloc_node();
+            let new_right_idx = self.alloc_node();
+            self.nodes[new_left_idx] = Some(Leaf(0));
+            self.nodes[new_right_idx] = Some(Leaf(0));
+            self.nodes.swap(0, new_left_idx);
+            self.nodes[0] = Some(Branch([new_left_idx, new_right_idx]));
+            self.log_size += 1;
+        }
+    }
+
+    fn set_range_descend(
+        &mut self,
+        node_idx: usize,
+        node_offset: usize,
+        node_log_size: u32,
+        start: usize,
+        end: usize,
+        x: usize,
+    ) {
+        let node_end = node_offset + (1 << node_log_size);
+        if end <= node_offset || node_end <= start {
+            return;
+        }
+        let node = self.nodes[node_idx].unwrap();
+        if start <= node_offset && node_end <= end {
+            if let Branch(children) = node {
+                for child_idx in children {
+                    self.free_subtree(child_idx);
+                }
+            }
+            self.nodes[node_idx] = Some(Leaf(x));
+            return;
+        }
+        let children = match node {
+            Leaf(old_value) if old_value == x => return,
+            Leaf(old_value) => {
+                let new_left_idx = self.alloc_node();
+                self.nodes[new_left_idx] = Some(Leaf(old_value));
+                let new_right_idx = self.alloc_node();
+                self.nodes[new_right_idx] = Some(Leaf(old_value));
+                let children = [new_left_idx, new_right_idx];
+                self.nodes[node_idx] = Some(Branch(children));
+                children
+            }
+            Branch(children) => children,
+        };
+        let half_size = 1 << (node_log_size - 1);
+        for (i, child_idx) in children.into_iter().enumerate() {
+            let child_offset = node_offset + i * half_size;
+            self.set_range_descend(child_idx, child_offset, node_log_size - 1, start, end, x);
+        }
+        if let Some((y, children)) = self.can_merge(self.nodes[node_idx].unwrap()) {
+            for child_idx in children {
+                self.free_node(child_idx);
+            }
+            self.nodes[node_idx] = Some(Leaf(y));
+        }
+    }
+
+    fn free_subtree(&mut self, i: usize) {
+        if let Some(Branch(children)) = self.nodes[i] {
+            for child_idx in children {
+                self.free_subtree(child_idx);
+            }
+        }
+        self.free_node(i);
+    }
+
     fn descend(&self, idx: usize) -> (usize, u32, Vec<usize>) {
         let mut node_idx = 0;
         let mut node_log_size = self.log_size;
@@ -168,8 +301,8 @@ impl VoxelBintree {
     }
 
     pub fn shrinked(&self) -> Self {
-        let mut new_index = vec![0; self.nodes.len()];
-        let nodes = vec![todo!()];
+        let mut nodes = Vec::new();
+        self.shrinked_descend(&mut nodes, 0);
         Self {
             nodes,
             free_nodes: Vec::new(),
@@ -181,10 +314,55 @@ impl VoxelBintree {
         let new = self.shrinked();
         *self = new;
     }
+
+    // Copies the subtree in pre-order, so the root stays at 0
+    fn shrinked_descend(&self, nodes: &mut Vec<Option<Node>>, node_idx: usize) -> usize {
+        let new_idx = nodes.len();
+        nodes.push(None);
+        nodes[new_idx] = Some(match self.nodes[node_idx].unwrap() {
+            Leaf(x) => Leaf(x),
+            Branch(children) => Branch(children.map(|i| self.shrinked_descend(nodes, i))),
+        });
+        new_idx
+    }
+}
+
+pub struct Runs<'a> {
+    tree: &'a VoxelBintree,
+    // Nodes yet to be visited as `(node_idx, offset, log_size)`
+    stack: Vec<(usize, usize, u32)>,
+    pending: Option<(usize, usize, usize)>,
+}
+
+impl Iterator for Runs<'_> {
+    type Item = (usize, usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node_idx, offset, log_size)) = self.stack.pop() {
+            match self.tree.nodes[node_idx].unwrap() {
+                Branch([left_idx, right_idx]) => {
+                    let half_size = 1 << (log_size - 1);
+                    self.stack
+                        .push((right_idx, offset + half_size, log_size - 1));
+                    self.stack.push((left_idx, offset, log_size - 1));
+                }
+                Leaf(x) => match &mut self.pending {
+                    Some((_, len, y)) if *y == x => *len += 1 << log_size,
+                    pending => {
+                        let run = pending.replace((offset, 1 << log_size, x));
+                        if run.is_some() {
+                            return run;
+                        }
+                    }
+                },
+            }
+        }
+        self.pending.take()
+    }
 }
 
 pub fn expand_bits_2(mut x: u64) -> u64 {
-    x = x & 0x0000_0000_FFFF_FFFF;
+    x &= 0x0000_0000_FFFF_FFFF;
     x = (x | (x << 16)) & 0x0000_FFFF_0000_FFFF;
     x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF;
     x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
@@ -194,7 +372,7 @@ pub fn expand_bits_2(mut x: u64) -> u64 {
 }
 
 pub fn shrink_bits_2(mut x: u64) -> u64 {
-    x = x & 0x5555_5555_5555_5555;
+    x &= 0x5555_5555_5555_5555;
     x = (x | (x >> 1)) & 0x3333_3333_3333_3333;
     x = (x | (x >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
     x = (x | (x >> 4)) & 0x00FF_00FF_00FF_00FF;
@@ -202,3 +380,86 @@ pub fn shrink_bits_2(mut x: u64) -> u64 {
     x = (x | (x >> 16)) & 0x0000_0000_FFFF_FFFF;
     x
 }
+
+pub fn expand_bits_3(mut x: u64) -> u64 {
+    x &= 0x0000_0000_001F_FFFF;
+    x = (x | (x << 32)) & 0x001F_0000_0000_FFFF;
+    x = (x | (x << 16)) & 0x001F_0000_FF00_00FF;
+    x = (x | (x << 8)) & 0x100F_00F0_0F00_F00F;
+    x = (x | (x << 4)) & 0x10C3_0C30_C30C_30C3;
+    x = (x | (x << 2)) & 0x1249_2492_4924_9249;
+    x
+}
+
+pub fn shrink_bits_3(mut x: u64) -> u64 {
+    x &= 0x1249_2492_4924_9249;
+    x = (x | (x >> 2)) & 0x10C3_0C30_C30C_30C3;
+    x = (x | (x >> 4)) & 0x100F_00F0_0F00_F00F;
+    x = (x | (x >> 8)) & 0x001F_0000_FF00_00FF;
+    x = (x | (x >> 16)) & 0x001F_0000_0000_FFFF;
+    x = (x | (x >> 32)) & 0x0000_0000_001F_FFFF;
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift64, good enough to shuffle edits around
+    fn next_random(state: &mut u64) -> usize {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state as usize
+    }
+
+    fn reference_runs(values: &[usize]) -> Vec<(usize, usize, usize)> {
+        let mut runs: Vec<(usize, usize, usize)> = Vec::new();
+        for (i, &x) in values.iter().enumerate() {
+            match runs.last_mut() {
+                Some((_, len, y)) if *y == x => *len += 1,
+                _ => runs.push((i, 1, x)),
+            }
+        }
+        runs
+    }
+
+    fn assert_matches(tree: &VoxelBintree, values: &[usize]) {
+        assert_eq!(tree.size(), values.len());
+        for (i, &x) in values.iter().enumerate() {
+            assert_eq!(tree.get(i), x, "value at {i}");
+        }
+        assert_eq!(tree.get(values.len()), 0);
+        assert_eq!(tree.runs().collect::<Vec<_>>(), reference_runs(values));
+    }
+
+    #[test]
+    fn random_set_range() {
+        let mut state = 0x9E37_79B9_7F4A_7C15;
+        for _ in 0..200 {
+            let mut tree = VoxelBintree::new();
+            let mut values = vec![0];
+            for _ in 0..50 {
+                let start = next_random(&mut state) % 100;
+                let len = next_random(&mut state) % 40;
+                let x = next_random(&mut state) % 3;
+                tree.set_range(start, len, x);
+                if len > 0 {
+                    while values.len() < start + len {
+                        values.resize(2 * values.len(), 0);
+                    }
+                    values[start..start + len].fill(x);
+                }
+                assert_matches(&tree, &values);
+
+                let shrinked = tree.shrinked();
+                assert!(shrinked.nodes.iter().all(Option::is_some));
+                assert_matches(&shrinked, &values);
+            }
+            assert_eq!(
+                VoxelBintree::from_slice(&values).shrinked(),
+                tree.shrinked()
+            );
+        }
+    }
+}
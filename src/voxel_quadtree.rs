@@ -0,0 +1,64 @@
+use crate::voxel_bintree::{expand_bits_2, shrink_bits_2, VoxelBintree};
+
+// 2D voxels stored in a `VoxelBintree` by their Morton code,
+// so every two levels of the bintree form one level of a quadtree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoxelQuadtree {
+    tree: VoxelBintree,
+}
+
+pub fn morton_2([x, y]: [usize; 2]) -> usize {
+    (expand_bits_2(x as _) | (expand_bits_2(y as _) << 1)) as _
+}
+
+pub fn unmorton_2(code: usize) -> [usize; 2] {
+    [
+        shrink_bits_2(code as _) as _,
+        shrink_bits_2((code >> 1) as _) as _,
+    ]
+}
+
+impl VoxelQuadtree {
+    pub fn new() -> Self {
+        Self {
+            tree: VoxelBintree::new(),
+        }
+    }
+
+    // Bulk load of values already in Z-order, index `i` goes to `unmorton_2(i)`
+    pub fn from_morton(values: &[usize]) -> Self {
+        Self {
+            tree: VoxelBintree::from_slice(values),
+        }
+    }
+
+    // Bulk load of a row-major square with the side being a power of two
+    pub fn from_rows(side: usize, values: &[usize]) -> Self {
+        assert!(side.is_power_of_two());
+        assert_eq!(values.len(), side * side);
+        let mut morton = vec![0; values.len()];
+        for (i, &value) in values.iter().enumerate() {
+            morton[morton_2([i % side, i / side])] = value;
+        }
+        Self::from_morton(&morton)
+    }
+
+    pub fn get(&self, p: [usize; 2]) -> usize {
+        self.tree.get(morton_2(p))
+    }
+
+    pub fn set(&mut self, p: [usize; 2], x: usize) {
+        self.tree.set(morton_2(p), x);
+    }
+
+    // Square `offset + [0, 2^log_extent)^2` aligned to its size is one Morton range
+    pub fn set_aligned(&mut self, offset: [usize; 2], log_extent: u32, x: usize) {
+        assert_eq!((offset[0] | offset[1]) & ((1 << log_extent) - 1), 0);
+        self.tree
+            .set_range(morton_2(offset), 1 << (2 * log_extent), x);
+    }
+
+    pub fn shrink(&mut self) {
+        self.tree.shrink();
+    }
+}